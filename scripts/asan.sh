@@ -0,0 +1,14 @@
+#!/bin/sh
+# Run the unit tests under AddressSanitizer, which fails on any use after
+# free, out of bounds access or leak in Rust and in mpc. Needs a nightly
+# toolchain, extra arguments are passed to `cargo test`.
+set -e
+
+target=$(rustc +nightly -vV | sed -n 's/^host: //p')
+
+# mpc is built by build.rs with the same sanitizer, lints
+# are left to the regular build
+CFLAGS="-fsanitize=address -fno-omit-frame-pointer" \
+RUSTFLAGS="-Zsanitizer=address --cap-lints warn" \
+ASAN_OPTIONS="detect_leaks=1" \
+	cargo +nightly test --lib --target "$target" "$@"
@@ -3,14 +3,25 @@
 
 use colors::*;
 use mpc_c_types::*;
+use std::fmt;
+use std::io;
+use std::ptr;
 use std::slice;
 
-/// The Ast type
-#[derive(Clone, Eq, PartialEq)]
+pub use diff::diff;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// The Ast type. Comparing and cloning ignore `raw_ast`, clones
+/// don't refer to the raw tree
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ast
 {
-	/// Pointer to the underlying Ast
-	/// It is not recommended to modify it
+	/// Pointer to the underlying Ast, it is not recommended to modify it.
+	/// The Ast does not own the pointer, it is valid only as long as
+	/// the `tree::ParseTree` it was copied from and a nullptr otherwise
+	#[cfg_attr(feature = "serde", serde(skip, default = "ptr::null_mut"))]
 	pub raw_ast: *mut mpc_ast_t,
 	/// Tag of this node. Folded tags are connected with
 	/// the pipe character `|`, tags of nodes with children
@@ -28,6 +39,44 @@ pub struct Ast
 	pub column: usize,
 	/// Total position in the file, only valid for nodes with no children
 	pub position: usize,
+	/// Bytes of the input covered by this node, valid for all nodes
+	pub span: Span,
+}
+
+/// A range of bytes in the parsed input, `end` being exclusive
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span
+{
+	/// Offset of the first byte
+	pub start: usize,
+	/// Offset one past the last byte
+	pub end: usize,
+}
+
+impl Span
+{
+	/// Number of bytes covered
+	pub fn len(&self) -> usize
+	{
+		self.end - self.start
+	}
+
+	/// True if no bytes are covered
+	pub fn is_empty(&self) -> bool
+	{
+		self.start == self.end
+	}
+
+	/// Smallest span covering both `self` and `other`
+	pub fn merge(&self, other: Span) -> Span
+	{
+		Span
+		{
+			start: if self.start < other.start { self.start } else { other.start },
+			end: if self.end > other.end { self.end } else { other.end },
+		}
+	}
 }
 
 /// A temporary type to represent a node returned
@@ -46,34 +95,79 @@ pub struct Child<'a>
 
 impl Ast
 {
-	/// Create a new Ast from a raw `mpc_ast_t`
+	/// Create a new Ast from a raw `mpc_ast_t`. The raw tree is copied
+	/// but not freed, prefer `tree::ParseTree::into_ast`
 	pub fn new(ast_ptr: *mut mpc_ast_t) -> Ast
 	{
 		unsafe
 		{
 			let mut children: Vec<Ast> = Vec::new();
 
-			for node in
-				slice::from_raw_parts((*ast_ptr).children, (*ast_ptr).children_num as usize)
+			if (*ast_ptr).children_num > 0
 			{
-				children.push(Ast::new(*node));
+				for node in
+					slice::from_raw_parts((*ast_ptr).children, (*ast_ptr).children_num as usize)
+				{
+					children.push(Ast::new(*node));
+				}
 			}
 
-			Ast
+			// spans count the bytes of the input, not of the lossy contents
+			let contents_len = (*ast_ptr).contents_len as usize;
+			let contents = String::from_utf8_lossy(
+				slice::from_raw_parts((*ast_ptr).contents as *const u8, contents_len)
+			).into_owned();
+			let position = dfu!(ast_ptr, state.pos);
+
+			let mut ast = Ast
 			{
 				raw_ast: ast_ptr,
 				tag: dfs!(ast_ptr, tag),
-				contents: dfs!(ast_ptr, contents),
+				contents: contents,
 				children: children,
 				row: dfu!(ast_ptr, state.row),
 				column: dfu!(ast_ptr, state.col),
-				position: dfu!(ast_ptr, state.pos),
-			}
+				position: position,
+				span: Span::default(),
+			};
+			ast.span = ast.covering_span(contents_len);
+			ast
+		}
+	}
+
+	/// Span of this node computed from its position and the number of
+	/// bytes its contents took in the input, or from the spans of its children
+	pub(crate) fn covering_span(&self, contents_len: usize) -> Span
+	{
+		// leaves know where they are, inner nodes cover their children
+		match self.children.split_first()
+		{
+			None => Span { start: self.position, end: self.position + contents_len },
+			Some((first, rest)) => rest.iter().fold(first.span, |span, c| span.merge(c.span)),
+		}
+	}
+
+	/// Set `raw_ast` of this node and all of its descendants to a nullptr
+	pub(crate) fn forget_raw(&mut self)
+	{
+		// explicit stack so that deep trees can't overflow it
+		let mut stack = vec![self];
+		while let Some(ast) = stack.pop()
+		{
+			ast.raw_ast = ptr::null_mut();
+			stack.extend(ast.children.iter_mut());
 		}
 	}
 
+	/// The part of `input` covered by this node. `input` has to be
+	/// the text this Ast was parsed from, otherwise this may panic
+	pub fn source_text<'a>(&self, input: &'a str) -> &'a str
+	{
+		&input[self.span.start..self.span.end]
+	}
+
 	/// Find a child by index
-	pub fn by_index(&self, index: usize) -> Option<Child>
+	pub fn by_index(&self, index: usize) -> Option<Child<'_>>
 	{
 		if index < self.children.len()
 		{
@@ -87,81 +181,343 @@ impl Ast
 		else { None }
 	}
 
+	/// Check whether this node has tag `tag`. Folded tags
+	/// such as `expr|term|regex` match any of their components
+	/// as well as the whole tag
+	pub fn has_tag(&self, tag: &str) -> bool
+	{
+		self.tag == tag || self.tag.split('|').any(|t| t == tag)
+	}
+
+	/// Check whether any component of this node's tag starts with `prefix`
+	pub fn has_tag_prefix(&self, prefix: &str) -> bool
+	{
+		self.tag.split('|').any(|t| t.starts_with(prefix))
+	}
+
+	/// Find the first child satisfying `f`
+	fn find_child<F>(&self, f: F) -> Option<Child<'_>>
+		where F: Fn(&Ast) -> bool
+	{
+		self.children
+			.iter()
+			.position(f)
+			.and_then(|index| self.by_index(index))
+	}
+
 	/// Find a child by tag
-	pub fn by_tag(&self, tag: &str) -> Option<Child>
+	pub fn by_tag(&self, tag: &str) -> Option<Child<'_>>
 	{
-		let children = self.children.clone().into_iter();
-		let index: i32 =
-			if let Some(_) =
-				children.clone().filter(|x| x.tag == tag).next()
-					{ children.count() as i32 }
-			else { -1 };
+		self.find_child(|x| x.has_tag(tag))
+	}
 
-		if index > -1
-		{
-			Some(Child
+	/// Find a child by contents
+	pub fn by_contents(&self, contents: &str) -> Option<Child<'_>>
+	{
+		self.find_child(|x| x.contents == contents)
+	}
+
+	/// Find a child with a tag starting with `prefix`
+	pub fn by_tag_prefix(&self, prefix: &str) -> Option<Child<'_>>
+	{
+		self.find_child(|x| x.has_tag_prefix(prefix))
+	}
+
+	/// Iterate over all children with tag `tag`
+	pub fn children_by_tag<'a, 'b>(&'a self, tag: &'b str) -> impl Iterator<Item = Child<'a>> + 'b
+		where 'a: 'b
+	{
+		self.children
+			.iter()
+			.enumerate()
+			.filter(move |&(_, x)| x.has_tag(tag))
+			.map(move |(index, x)| Child
 			{
 				parent: self,
-				ast: &self.children[index as usize],
-				index: index as usize
+				ast: x,
+				index: index,
 			})
-		}
-		else { None }
 	}
 
-	/// Find a child by contents
-	pub fn by_contents(&self, contents: &str) -> Option<Child>
+	/// Find the `n`-th child (counting from zero) with tag `tag`
+	pub fn nth_by_tag(&self, tag: &str, n: usize) -> Option<Child<'_>>
 	{
-		let children = self.children.clone().into_iter();
-		let index: i32 =
-			if let Some(_) =
-				children.clone().filter(|x| x.contents == contents).next()
-					{ children.count() as i32 }
-			else { -1 };
+		self.children_by_tag(tag).nth(n)
+	}
 
-		if index > -1
+	/// Find the first descendant with tag `tag`, searching depth-first.
+	/// This node itself is not considered
+	pub fn find_descendant(&self, tag: &str) -> Option<Child<'_>>
+	{
+		// explicit stack so that deep trees can't overflow it
+		let mut stack: Vec<(&Ast, usize)> = (0..self.children.len()).rev().map(|i| (self, i)).collect();
+		while let Some((parent, index)) = stack.pop()
 		{
-			Some(Child
+			let node = &parent.children[index];
+			if node.has_tag(tag)
 			{
-				parent: self,
-				ast: &self.children[index as usize],
-				index: index as usize
-			})
+				return Some(Child
+				{
+					parent: parent,
+					ast: node,
+					index: index,
+				});
+			}
+			stack.extend((0..node.children.len()).rev().map(|i| (node, i)));
 		}
-		else { None }
+		None
+	}
+
+	/// Iterate over this node and all of its descendants in pre-order,
+	/// yielding each node with its depth below this one
+	pub fn iter_preorder(&self) -> PreOrder<'_>
+	{
+		PreOrder { stack: vec![(0, self)] }
+	}
+
+	/// Iterate over this node and all of its descendants in post-order,
+	/// yielding each node with its depth below this one
+	pub fn iter_postorder(&self) -> PostOrder<'_>
+	{
+		PostOrder { stack: vec![(0, self, 0)] }
 	}
 
-	/// Print the Ast
+	/// Print the Ast to `stdout` in color, see `Ast::write_to`
 	pub fn print(&self)
 	{
 		self.print_level(0);
 	}
 
-	/// Print the Ast with starting depth (indentation) `level`
+	/// Print the Ast to `stdout` in color with starting depth (indentation) `level`
 	pub fn print_level(&self, level: usize)
 	{
-		for _ in 0..level {trace!("  ");}
-		if self.children.is_empty()
+		let stdout = io::stdout();
+		let _ = self.write_level(&mut stdout.lock(), &PrintOptions::default().colors(true), level);
+	}
+
+	/// Write the Ast as an indented tree the way `mpc_ast_print` does,
+	/// one node per line. Leaves are written as `tag:row:column 'contents'`
+	///
+	/// ```text
+	/// >
+	///   sentence|>
+	///     word|regex:1:1 'Bananas'
+	///     punct|char:1:8 '.'
+	/// ```
+	pub fn write_to<W: io::Write + ?Sized>(&self, w: &mut W, options: PrintOptions) -> io::Result<()>
+	{
+		self.write_level(w, &options, 0)
+	}
+
+	fn write_level<W: io::Write + ?Sized>(&self, w: &mut W, options: &PrintOptions, level: usize) -> io::Result<()>
+	{
+		for (depth, node) in self.iter_preorder()
 		{
-			trace!(
-				RED self.tag
-				RESET ':'
-				GREEN self.row
-				RESET ':'
-				YELLOW self.column " "
-				RESET '\''
-				self.contents "'\n"
-			);
+			write!(w, "{}", Line { ast: node, options: options, depth: level + depth })?;
 		}
-		else
+		w.flush()
+	}
+}
+
+/// Options of `Ast::write_to`, plain with positions
+/// and an indentation of two spaces by default
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrintOptions
+{
+	/// Use ANSI colors
+	pub colors: bool,
+	/// Write row and column of leaves
+	pub positions: bool,
+	/// Number of spaces per level of depth
+	pub indent: usize,
+}
+
+impl PrintOptions
+{
+	/// Enable or disable ANSI colors
+	pub fn colors(mut self, colors: bool) -> PrintOptions
+	{
+		self.colors = colors;
+		self
+	}
+
+	/// Enable or disable rows and columns of leaves
+	pub fn positions(mut self, positions: bool) -> PrintOptions
+	{
+		self.positions = positions;
+		self
+	}
+
+	/// Set the number of spaces per level of depth
+	pub fn indent(mut self, indent: usize) -> PrintOptions
+	{
+		self.indent = indent;
+		self
+	}
+}
+
+impl Default for PrintOptions
+{
+	fn default() -> PrintOptions
+	{
+		PrintOptions
+		{
+			colors: false,
+			positions: true,
+			indent: 2,
+		}
+	}
+}
+
+/// A single node written by `Ast::write_to`, including its newline
+struct Line<'a>
+{
+	ast: &'a Ast,
+	options: &'a PrintOptions,
+	depth: usize,
+}
+
+impl<'a> fmt::Display for Line<'a>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let (ast, o) = (self.ast, self.options);
+		write!(f, "{:1$}", "", self.depth * o.indent)?;
+
+		if !ast.children.is_empty()
+		{
+			return writeln!(f, "{}{}{}", paint(o.colors, MAGENTA), ast.tag, paint(o.colors, RESET));
+		}
+
+		write!(f, "{}{}{}", paint(o.colors, RED), ast.tag, paint(o.colors, RESET))?;
+		if o.positions
 		{
-			trace!(
-				MAGENTA self.tag
-				RESET "\n"
-			);
-			for child in &self.children
+			write!(f, ":{}{}{}:{}{}{}",
+				paint(o.colors, GREEN), ast.row + 1, paint(o.colors, RESET),
+				paint(o.colors, YELLOW), ast.column + 1, paint(o.colors, RESET))?;
+		}
+		writeln!(f, " '{}'", ast.contents)
+	}
+}
+
+impl fmt::Display for Ast
+{
+	/// The tree written by `Ast::write_to` with default options
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let options = PrintOptions::default();
+		for (depth, node) in self.iter_preorder()
+		{
+			write!(f, "{}", Line { ast: node, options: &options, depth: depth })?;
+		}
+		Ok(())
+	}
+}
+
+impl Clone for Ast
+{
+	fn clone(&self) -> Ast
+	{
+		Ast
+		{
+			raw_ast: ptr::null_mut(),
+			tag: self.tag.clone(),
+			contents: self.contents.clone(),
+			children: self.children.clone(),
+			row: self.row,
+			column: self.column,
+			position: self.position,
+			span: self.span,
+		}
+	}
+}
+
+impl PartialEq for Ast
+{
+	/// All fields except `raw_ast`, so that copies of different
+	/// raw trees compare equal
+	fn eq(&self, other: &Ast) -> bool
+	{
+		self.tag == other.tag
+			&& self.contents == other.contents
+			&& self.row == other.row
+			&& self.column == other.column
+			&& self.position == other.position
+			&& self.span == other.span
+			&& self.children == other.children
+	}
+}
+
+impl Eq for Ast {}
+
+impl fmt::Debug for Ast
+{
+	/// All fields except `raw_ast`, which has no meaning outside of mpc
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		f.debug_struct("Ast")
+			.field("tag", &self.tag)
+			.field("contents", &self.contents)
+			.field("row", &self.row)
+			.field("column", &self.column)
+			.field("position", &self.position)
+			.field("span", &self.span)
+			.field("children", &self.children)
+			.finish()
+	}
+}
+
+/// Pre-order iterator over an Ast, see `Ast::iter_preorder`
+pub struct PreOrder<'a>
+{
+	/// nodes yet to be visited along with their depth
+	stack: Vec<(usize, &'a Ast)>,
+}
+
+impl<'a> Iterator for PreOrder<'a>
+{
+	type Item = (usize, &'a Ast);
+
+	fn next(&mut self) -> Option<(usize, &'a Ast)>
+	{
+		let (depth, node) = self.stack.pop()?;
+		self.stack.extend(node.children.iter().rev().map(|c| (depth + 1, c)));
+		Some((depth, node))
+	}
+}
+
+/// Post-order iterator over an Ast, see `Ast::iter_postorder`
+pub struct PostOrder<'a>
+{
+	/// path from the root to the current node, along with
+	/// the index of the next child to visit
+	stack: Vec<(usize, &'a Ast, usize)>,
+}
+
+impl<'a> Iterator for PostOrder<'a>
+{
+	type Item = (usize, &'a Ast);
+
+	fn next(&mut self) -> Option<(usize, &'a Ast)>
+	{
+		loop
+		{
+			let (depth, node, next) =
+			{
+				let top = self.stack.last_mut()?;
+				let current = *top;
+				top.2 += 1;
+				current
+			};
+
+			if next < node.children.len()
+			{
+				self.stack.push((depth + 1, &node.children[next], 0));
+			}
+			else
 			{
-				child.print_level(level+1);
+				self.stack.pop();
+				return Some((depth, node));
 			}
 		}
 	}
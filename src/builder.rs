@@ -0,0 +1,140 @@
+//! Contains a builder of `Ast`s and their conversion back to `mpc_ast_t`
+#![allow(dead_code)]
+
+use ast::{Ast, Span};
+use mpc_c::*;
+use mpc_c_types::*;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use tree::ParseTree;
+
+/// Fluent builder of an `Ast`, mostly useful for writing down
+/// expected trees. See also the `ast!` macro
+///
+/// ```rust
+/// # extern crate mpc;
+/// # use mpc::builder::AstBuilder;
+/// # fn main() {
+/// let ast = AstBuilder::new("pair|>")
+///     .child(AstBuilder::new("char").contents("("))
+///     .child(AstBuilder::new("word|regex").contents("a").at(0, 1, 1))
+///     .child(AstBuilder::new("char").contents(")").at(0, 2, 2))
+///     .build();
+/// assert_eq!(ast.span.end, 3);
+/// # }
+/// ```
+pub struct AstBuilder
+{
+	ast: Ast,
+}
+
+impl AstBuilder
+{
+	/// Start building a node with tag `tag`, no contents and no children
+	pub fn new(tag: &str) -> AstBuilder
+	{
+		AstBuilder
+		{
+			ast: Ast
+			{
+				raw_ast: ptr::null_mut(),
+				tag: tag.to_owned(),
+				contents: String::new(),
+				children: Vec::new(),
+				row: 0,
+				column: 0,
+				position: 0,
+				span: Span::default(),
+			}
+		}
+	}
+
+	/// Set the contents of the node
+	pub fn contents(mut self, contents: &str) -> AstBuilder
+	{
+		self.ast.contents = contents.to_owned();
+		self
+	}
+
+	/// Set the location of the node, all of them starting from 0
+	pub fn at(mut self, row: usize, column: usize, position: usize) -> AstBuilder
+	{
+		self.ast.row = row;
+		self.ast.column = column;
+		self.ast.position = position;
+		self
+	}
+
+	/// Append a child node
+	pub fn child<A: Into<Ast>>(mut self, child: A) -> AstBuilder
+	{
+		self.ast.children.push(child.into());
+		self
+	}
+
+	/// Finish the node, its span is computed like the span of a parsed one
+	pub fn build(mut self) -> Ast
+	{
+		self.ast.span = self.ast.covering_span(self.ast.contents.len());
+		self.ast
+	}
+}
+
+impl From<AstBuilder> for Ast
+{
+	fn from(builder: AstBuilder) -> Ast
+	{
+		builder.build()
+	}
+}
+
+impl Ast
+{
+	/// Allocate an equivalent tree on the C side, which can be compared
+	/// with parsed trees using `mpc_ast_eq`. `raw_ast` pointers of this
+	/// Ast are left untouched.
+	///
+	/// Panics if a tag contains a nul byte
+	pub fn to_raw(&self) -> ParseTree
+	{
+		unsafe { ParseTree::from_raw(raw_node(self)) }
+	}
+}
+
+/// Allocate `ast` and all its descendants with `mpc_ast_new`
+fn raw_node(ast: &Ast) -> *mut mpc_ast_t
+{
+	let root = raw_leaf(ast);
+	// explicit stack so that deep trees can't overflow it, holds nodes
+	// along with their allocated parent. Children are pushed last first
+	// so that each parent gets them in order
+	let mut stack: Vec<(&Ast, *mut mpc_ast_t)> = ast.children.iter().rev().map(|c| (c, root)).collect();
+
+	while let Some((ast, parent)) = stack.pop()
+	{
+		let node = raw_leaf(ast);
+		unsafe { mpc_ast_add_child(parent, node); }
+		stack.extend(ast.children.iter().rev().map(|c| (c, node)));
+	}
+	root
+}
+
+/// Allocate `ast` alone with `mpc_ast_new`, without its children
+fn raw_leaf(ast: &Ast) -> *mut mpc_ast_t
+{
+	let tag = CString::new(ast.tag.as_str()).expect("nul byte in Ast tag");
+
+	unsafe
+	{
+		let node = mpc_ast_new(tag.as_ptr(), b"\0".as_ptr() as *const c_char);
+		mpc_ast_contents(node, ast.contents.as_ptr() as *const c_char, ast.contents.len() as i64);
+		mpc_ast_state(node, mpc_state_t
+		{
+			pos: ast.position as i64,
+			row: ast.row as i64,
+			col: ast.column as i64,
+		});
+		node
+	}
+}
@@ -0,0 +1,342 @@
+//! Contains trampolines through which Rust closures are used as mpc's
+//! callbacks. The closure is passed to mpc as the `void*` of the
+//! `*_to` variants of its functions and values are passed around as
+//! `Box`es, so that parsers produce Rust values instead of strings.
+//!
+//! Panics can't unwind through C, so the trampolines catch them and
+//! produce a nullptr instead of a value. The parse goes on, and its
+//! caller reports the panic once mpc returns, see `take_panic`
+#![allow(dead_code)]
+
+use error::ParseError;
+use mpc_c::*;
+use mpc_c_types::*;
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+thread_local!
+{
+	/// payload of the first panic caught since the last `take_panic`
+	static PANIC: RefCell<Option<Box<dyn Any + Send>>> = RefCell::new(None);
+}
+
+/// Run `f`, catching a panic to be reported by `take_panic`
+fn guard<R, F: FnOnce() -> R>(f: F) -> Option<R>
+{
+	match panic::catch_unwind(AssertUnwindSafe(f))
+	{
+		Ok(r) => Some(r),
+		Err(payload) =>
+		{
+			PANIC.with(|p| { p.borrow_mut().get_or_insert(payload); });
+			None
+		}
+	}
+}
+
+/// Message of the first panic caught in a callback since the last call,
+/// if any. Parsers call it before parsing, to forget earlier panics,
+/// and after it, to turn a panic into a parse failure
+pub fn take_panic() -> Option<String>
+{
+	PANIC.with(|p| p.borrow_mut().take()).map(|payload|
+		match payload.downcast::<String>()
+		{
+			Ok(s) => *s,
+			Err(payload) => match payload.downcast::<&str>()
+			{
+				Ok(s) => (*s).to_owned(),
+				Err(_) => "Box<dyn Any>".to_owned(),
+			},
+		})
+}
+
+/// The failure reported by a parse during which a callback panicked
+pub fn panic_error(filename: &str, message: &str) -> ParseError
+{
+	ParseError::failure(filename, format!("callback panicked: {}", message))
+}
+
+/// A boxed closure handed to mpc, freed when dropped.
+/// It has to outlive every parser it was passed to
+pub struct Callback
+{
+	ptr: *mut c_void,
+	drop: unsafe fn(*mut c_void),
+}
+
+impl Callback
+{
+	/// Box `f`
+	pub fn new<F: 'static>(f: F) -> Callback
+	{
+		Callback
+		{
+			ptr: Box::into_raw(Box::new(f)) as *mut c_void,
+			drop: drop_closure::<F>,
+		}
+	}
+
+	/// Pointer to the closure, to be passed as the `x` of mpc's functions
+	pub fn as_ptr(&self) -> *mut c_void
+	{
+		self.ptr
+	}
+}
+
+impl Drop for Callback
+{
+	fn drop(&mut self)
+	{
+		unsafe { (self.drop)(self.ptr); }
+	}
+}
+
+unsafe fn drop_closure<F>(ptr: *mut c_void)
+{
+	drop(Box::from_raw(ptr as *mut F));
+}
+
+/// Box `value` as a `mpc_val_t`
+fn into_val<T>(value: T) -> *mut mpc_val_t
+{
+	Box::into_raw(Box::new(value)) as *mut mpc_val_t
+}
+
+/// `mpc_apply_to_t` calling a `F` on the string produced by a parser.
+/// The string is freed, the result is boxed, or a nullptr if `F` panicked
+///
+/// # Safety
+///
+/// `val` must be a nul-terminated string allocated by mpc and `x` must
+/// point to a live `F`
+pub unsafe extern "C" fn apply_str<F, T>(val: *mut mpc_val_t, x: *mut c_void) -> *mut mpc_val_t
+	where F: Fn(&str) -> T
+{
+	let f = &*(x as *const F);
+	let out = guard(|| f(&CStr::from_ptr(val as *const c_char).to_string_lossy()));
+	free(val);
+	out.map_or(ptr::null_mut(), into_val)
+}
+
+/// `mpc_apply_to_t` calling a `F` on a boxed value, the result is boxed.
+/// A nullptr, produced by an earlier panic, is passed on without calling `F`
+///
+/// # Safety
+///
+/// `val` must be a `Box<A>` or a nullptr and `x` must point to a live `F`
+pub unsafe extern "C" fn apply_boxed<F, A, B>(val: *mut mpc_val_t, x: *mut c_void) -> *mut mpc_val_t
+	where F: Fn(A) -> B
+{
+	if val.is_null()
+	{
+		return val;
+	}
+	let f = &*(x as *const F);
+	let a = *Box::from_raw(val as *mut A);
+	guard(|| f(a)).map_or(ptr::null_mut(), into_val)
+}
+
+/// `mpc_fold_t` collecting boxed values into a boxed `Vec`, skipping
+/// the nullptrs of parsers such as `mpc_maybe`
+///
+/// # Safety
+///
+/// `xs` must hold `n` values, each a `Box<T>` or a nullptr
+pub unsafe extern "C" fn fold_vec<T>(n: i32, xs: *mut *mut mpc_val_t) -> *mut mpc_val_t
+{
+	let mut values: Vec<T> = Vec::with_capacity(n as usize);
+	for i in 0..n as usize
+	{
+		let x = *xs.add(i);
+		if !x.is_null()
+		{
+			values.push(*Box::from_raw(x as *mut T));
+		}
+	}
+	into_val(values)
+}
+
+/// `mpc_fold_t` pairing the boxed values of two parsers, a nullptr
+/// if either of them is one
+///
+/// # Safety
+///
+/// `xs` must hold two values, a `Box<A>` and a `Box<B>`, or nullptrs
+pub unsafe extern "C" fn fold_pair<A, B>(_n: i32, xs: *mut *mut mpc_val_t) -> *mut mpc_val_t
+{
+	let (a, b) = (*xs as *mut A, *xs.add(1) as *mut B);
+	if a.is_null() || b.is_null()
+	{
+		drop_boxed::<A>(a as *mut mpc_val_t);
+		drop_boxed::<B>(b as *mut mpc_val_t);
+		return ptr::null_mut();
+	}
+	into_val((*Box::from_raw(a), *Box::from_raw(b)))
+}
+
+/// `mpc_apply_t` boxing the string produced by a parser as a `String`
+///
+/// # Safety
+///
+/// `val` must be a nul-terminated string allocated by mpc
+pub unsafe extern "C" fn apply_string(val: *mut mpc_val_t) -> *mut mpc_val_t
+{
+	let out = CStr::from_ptr(val as *const c_char).to_string_lossy().into_owned();
+	free(val);
+	into_val(out)
+}
+
+/// `mpc_apply_t` boxing the first character of the string produced by a parser
+///
+/// # Safety
+///
+/// `val` must be a nul-terminated string allocated by mpc
+pub unsafe extern "C" fn apply_char(val: *mut mpc_val_t) -> *mut mpc_val_t
+{
+	let out = CStr::from_ptr(val as *const c_char).to_string_lossy().chars().next().unwrap_or('\0');
+	free(val);
+	into_val(out)
+}
+
+/// `mpc_apply_t` boxing the first byte of the string produced by a parser
+///
+/// # Safety
+///
+/// `val` must be a nul-terminated string allocated by mpc
+pub unsafe extern "C" fn apply_byte(val: *mut mpc_val_t) -> *mut mpc_val_t
+{
+	let out = *(val as *const u8);
+	free(val);
+	into_val(out)
+}
+
+/// `mpc_apply_t` boxing a boxed value or a nullptr as an `Option`
+///
+/// # Safety
+///
+/// `val` must be a `Box<T>` or a nullptr
+pub unsafe extern "C" fn apply_option<T>(val: *mut mpc_val_t) -> *mut mpc_val_t
+{
+	if val.is_null() { into_val(None::<T>) }
+	else { into_val(Some(*Box::from_raw(val as *mut T))) }
+}
+
+/// `mpc_apply_t` returning the value unchanged
+///
+/// # Safety
+///
+/// Always safe, it is `unsafe` to fit the type mpc expects
+pub unsafe extern "C" fn identity(val: *mut mpc_val_t) -> *mut mpc_val_t
+{
+	val
+}
+
+/// `mpc_ctor_t` boxing `()`
+///
+/// # Safety
+///
+/// Always safe, it is `unsafe` to fit the type mpc expects
+pub unsafe extern "C" fn lift_unit() -> *mut mpc_val_t
+{
+	into_val(())
+}
+
+/// `mpc_dtor_t` dropping a boxed value, used to destroy
+/// partial results when mpc backtracks
+///
+/// # Safety
+///
+/// `val` must be a `Box<T>` or a nullptr, and not be used afterwards
+pub unsafe extern "C" fn drop_boxed<T>(val: *mut mpc_val_t)
+{
+	if !val.is_null()
+	{
+		drop(Box::from_raw(val as *mut T));
+	}
+}
+
+/// `mpc_satisfy_to_t` calling a `F` on a byte of input, the byte
+/// doesn't match if `F` panics
+///
+/// # Safety
+///
+/// `x` must point to a live `F`
+pub unsafe extern "C" fn satisfy<F>(c: c_char, x: *mut c_void) -> i32
+	where F: Fn(u8) -> bool
+{
+	let f = &*(x as *const F);
+	guard(|| f(c as u8)).unwrap_or(false) as i32
+}
+
+/// `mpc_anchor_to_t` calling a `F` on the bytes around the position,
+/// which are 0 at the start and at the end of input. The anchor
+/// doesn't match if `F` panics
+///
+/// # Safety
+///
+/// `x` must point to a live `F`
+pub unsafe extern "C" fn anchor<F>(last: c_char, next: c_char, x: *mut c_void) -> i32
+	where F: Fn(u8, u8) -> bool
+{
+	let f = &*(x as *const F);
+	guard(|| f(last as u8, next as u8)).unwrap_or(false) as i32
+}
+
+/// Apply `f` to the string produced by `a`, the parser produces a `Box<T>`
+///
+/// # Safety
+///
+/// `a` must be a valid parser producing a string, it is consumed. The
+/// returned `Callback` must outlive every use of the returned parser
+pub unsafe fn apply<F, T>(a: parser_ptr, f: F) -> (parser_ptr, Callback)
+	where F: Fn(&str) -> T + 'static
+{
+	let callback = Callback::new(f);
+	(mpc_apply_to(a, apply_str::<F, T>, callback.as_ptr()), callback)
+}
+
+/// Run `a`, which produces a `Box<T>`, zero or more times and fold the
+/// values with `f`. The parser produces a `Box<U>`
+///
+/// # Safety
+///
+/// `a` must be a valid parser producing a `Box<T>` and nothing else,
+/// it is consumed. The returned `Callback` must outlive every use of
+/// the returned parser
+pub unsafe fn fold<F, T, U>(a: parser_ptr, f: F) -> (parser_ptr, Callback)
+	where F: Fn(Vec<T>) -> U + 'static
+{
+	let callback = Callback::new(f);
+	let many = mpc_many(fold_vec::<T>, a);
+	(mpc_apply_to(many, apply_boxed::<F, Vec<T>, U>, callback.as_ptr()), callback)
+}
+
+/// Match a byte for which `f` returns true, the parser produces a string
+///
+/// # Safety
+///
+/// The returned `Callback` must outlive every use of the returned parser
+pub unsafe fn satisfy_with<F>(f: F) -> (parser_ptr, Callback)
+	where F: Fn(u8) -> bool + 'static
+{
+	let callback = Callback::new(f);
+	(mpc_satisfy_to(satisfy::<F>, callback.as_ptr()), callback)
+}
+
+/// Succeed without consuming input when `f` returns true for the last
+/// and the next byte, the parser produces a nullptr
+///
+/// # Safety
+///
+/// The returned `Callback` must outlive every use of the returned parser
+pub unsafe fn anchor_with<F>(f: F) -> (parser_ptr, Callback)
+	where F: Fn(u8, u8) -> bool + 'static
+{
+	let callback = Callback::new(f);
+	(mpc_anchor_to(anchor::<F>, callback.as_ptr()), callback)
+}
@@ -15,3 +15,9 @@ pub const MAGENTA  :&'static str = "\x1b[35m";
 pub const CYAN     :&'static str = "\x1b[36m";
 /// Reset to default ANSI color
 pub const RESET    :&'static str = "\x1b[0m";
+
+/// Returns `color` if `colors` is enabled, an empty string otherwise
+pub fn paint(colors: bool, color: &'static str) -> &'static str
+{
+	if colors { color } else { "" }
+}
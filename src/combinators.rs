@@ -0,0 +1,514 @@
+//! Contains a safe, chainable API for building parsers out of mpc's
+//! primitive parsers and combinators. Parsers produce the same trees
+//! as `mpca_lang` grammars, leaves being tagged after the primitive
+//! they were matched by.
+//!
+//! ```rust
+//! # extern crate mpc;
+//! use mpc::combinators as p;
+//! # fn main() {
+//! let statement = p::string("if").tok()
+//!     .then(p::ident().tok())
+//!     .or(p::string("return").tok().then(p::digits()))
+//!     .many();
+//!
+//! let tree = statement.parse("<input>", "if x return 1").unwrap();
+//! assert_eq!(tree.to_ast().children.len(), 4);
+//! # }
+//! ```
+//!
+//! Each `Combinator` owns the C parser it was built from, combining
+//! consumes the sub-parsers so that they are only ever freed by their
+//! parent. Recursive parsers are made of named rules owned by `Rules`.
+//! Functions taking strings panic if they contain a nul byte, except
+//! for the input of `parse`.
+#![allow(dead_code)]
+
+use callback::{self, Callback};
+use error::ParseError;
+use glue;
+use mpc_c::*;
+use mpc_c_types::*;
+use parser::{self, Parser};
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Not;
+use std::os::raw::c_char;
+use std::rc::Rc;
+use tree::ParseTree;
+
+/// A parser producing an Ast, `'a` being the lifetime of the `Rules`
+/// it refers to. Freed when dropped unless consumed by another one
+pub struct Combinator<'a>
+{
+	raw: parser_ptr,
+	owned: Owned,
+	rules: Invariant<'a>,
+}
+
+/// Ties parsers to the `Rules` they refer to. Invariant, so that parsers
+/// referring to different `Rules` can only be combined while all of them live
+pub(crate) type Invariant<'a> = PhantomData<Cell<&'a Rules>>;
+
+/// What the parsers point to, but mpc doesn't copy
+#[derive(Default)]
+pub(crate) struct Owned
+{
+	pub(crate) tags: Vec<CString>,
+	pub(crate) callbacks: Vec<Callback>,
+	/// rules referred to, kept alive as long as the parsers are
+	pub(crate) rules: Vec<Rc<RuleSet>>,
+}
+
+impl Owned
+{
+	pub(crate) fn append(&mut self, other: &mut Owned)
+	{
+		self.tags.append(&mut other.tags);
+		self.callbacks.append(&mut other.callbacks);
+		self.rules.append(&mut other.rules);
+	}
+}
+
+impl<'a> Combinator<'a>
+{
+	/// Take ownership of a raw parser producing an Ast
+	///
+	/// # Safety
+	///
+	/// `raw` must be a parser allocated by mpc whose outputs are
+	/// `mpc_ast_t`s, or a nullptr when it matches nothing. It is deleted
+	/// with `mpc_delete` on drop, so nothing else may free it, and the
+	/// strings and callbacks it refers to must outlive it
+	pub unsafe fn from_raw(raw: parser_ptr) -> Combinator<'a>
+	{
+		Combinator
+		{
+			raw: raw,
+			owned: Owned::default(),
+			rules: PhantomData,
+		}
+	}
+
+	/// Pointer to the underlying parser
+	pub fn as_ptr(&self) -> parser_ptr
+	{
+		self.raw
+	}
+
+	/// Give up the parser along with what it points to
+	fn into_parts(mut self) -> (parser_ptr, Owned)
+	{
+		let parts = (self.raw, mem::take(&mut self.owned));
+		mem::forget(self);
+		parts
+	}
+
+	/// Wrap the parser into the one returned by `f`
+	fn map<F>(self, f: F) -> Combinator<'a>
+		where F: FnOnce(parser_ptr) -> parser_ptr
+	{
+		let (raw, owned) = self.into_parts();
+		Combinator
+		{
+			raw: f(raw),
+			owned: owned,
+			rules: PhantomData,
+		}
+	}
+
+	/// Combine the parser with `other` into the one returned by `f`
+	fn join<F>(self, other: Combinator<'a>, f: F) -> Combinator<'a>
+		where F: FnOnce(parser_ptr, parser_ptr) -> parser_ptr
+	{
+		let (other, mut owned) = other.into_parts();
+		self.map(|raw| f(raw, other)).with(&mut owned)
+	}
+
+	fn with(mut self, owned: &mut Owned) -> Combinator<'a>
+	{
+		self.owned.append(owned);
+		self
+	}
+
+	/// Run this parser and then `other`
+	pub fn then<C: Into<Combinator<'a>>>(self, other: C) -> Combinator<'a>
+	{
+		self.join(other.into(), |a, b| unsafe { mpca_and(2, a, b) })
+	}
+
+	/// Run this parser or `other` if it fails
+	pub fn or<C: Into<Combinator<'a>>>(self, other: C) -> Combinator<'a>
+	{
+		self.join(other.into(), |a, b| unsafe { mpca_or(2, a, b) })
+	}
+
+	/// Run this parser zero or more times
+	pub fn many(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_many(raw) })
+	}
+
+	/// Run this parser one or more times
+	pub fn many1(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_many1(raw) })
+	}
+
+	/// Run this parser exactly `n` times
+	pub fn count(self, n: usize) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_count(n as i32, raw) })
+	}
+
+	/// Run this parser, succeeding without consuming input if it fails
+	pub fn maybe(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_maybe(raw) })
+	}
+
+	/// Skip whitespace following this parser
+	pub fn tok(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { glue::glue_tok(raw) })
+	}
+
+	/// Run this parser between the strings `open` and `close`
+	pub fn between(self, open: &str, close: &str) -> Combinator<'a>
+	{
+		string(open).then(self).then(string(close))
+	}
+
+	/// Match the whole input
+	pub fn total(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_total(raw) })
+	}
+
+	/// Run this parser with backtracking disabled
+	pub fn predictive(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpc_predictive(raw) })
+	}
+
+	/// Report `expected` instead of the parser's own expectations on failure
+	pub fn expect(self, expected: &str) -> Combinator<'a>
+	{
+		let expected = CString::new(expected).unwrap();
+		self.map(|raw| unsafe { mpc_expect(raw, expected.as_ptr()) })
+	}
+
+	/// Prepend `tag` to the tag of the resulting node, the way
+	/// rule names are prepended in grammars, e.g. `number|digits`
+	pub fn tag(self, tag: &str) -> Combinator<'a>
+	{
+		let tag = CString::new(tag).unwrap();
+		let ptr = tag.as_ptr();
+		self.map(|raw| unsafe { mpca_add_tag(raw, ptr) })
+			.with(&mut Owned { tags: vec![tag], ..Owned::default() })
+	}
+
+	/// Parse `input`, `filename` is used in error messages. A parser
+	/// matching no nodes, like a `many` matching nothing, produces
+	/// an empty node tagged `>`
+	pub fn parse(&self, filename: &str, input: &str) -> Result<ParseTree, ParseError>
+	{
+		let c_filename = glue::filename(filename);
+		callback::take_panic();
+		let res = unsafe
+		{
+			match glue::nparse(&c_filename, input.as_bytes(), self.raw)
+			{
+				Ok(a) if a.is_null() => Ok(ParseTree::from_raw(mpc_ast_new(c_tag(b">\0"), c_tag(b"\0")))),
+				Ok(a) => Ok(ParseTree::from_raw(a)),
+				Err(e) => Err(ParseError::from_raw(e)),
+			}
+		};
+		match callback::take_panic()
+		{
+			Some(message) => Err(callback::panic_error(filename, &message)),
+			None => res,
+		}
+	}
+}
+
+/// `!p` succeeds without consuming input only if `p` fails
+impl<'a> Not for Combinator<'a>
+{
+	type Output = Combinator<'a>;
+
+	fn not(self) -> Combinator<'a>
+	{
+		self.map(|raw| unsafe { mpca_not(raw) })
+	}
+}
+
+impl<'a> Drop for Combinator<'a>
+{
+	fn drop(&mut self)
+	{
+		// retained rules referred to are skipped by mpc_delete
+		unsafe { mpc_delete(self.raw); }
+	}
+}
+
+/// A static tag for `glue_leaf`
+fn c_tag(tag: &'static [u8]) -> *const c_char
+{
+	tag.as_ptr() as *const c_char
+}
+
+/// Turn a primitive parser into a combinator producing leaves tagged `tag`
+fn leaf<'a>(raw: parser_ptr, tag: &'static [u8]) -> Combinator<'a>
+{
+	unsafe { Combinator::from_raw(glue::glue_leaf(raw, c_tag(tag))) }
+}
+
+/// Match any character
+pub fn any<'a>() -> Combinator<'a> { leaf(unsafe { mpc_any() }, b"char\0") }
+
+/// Match the character `c`
+pub fn char<'a>(c: char) -> Combinator<'a>
+{
+	if c.is_ascii() { leaf(unsafe { mpc_char(c as c_char) }, b"char\0") }
+	else { leaf(unsafe { mpc_string(CString::new(c.to_string()).unwrap().as_ptr()) }, b"char\0") }
+}
+
+/// Match an ASCII character from `start` to `end`
+pub fn range<'a>(start: u8, end: u8) -> Combinator<'a>
+{
+	leaf(unsafe { mpc_range(start as c_char, end as c_char) }, b"char\0")
+}
+
+/// Match one of the bytes of `chars`
+pub fn oneof<'a>(chars: &str) -> Combinator<'a>
+{
+	let chars = CString::new(chars).unwrap();
+	leaf(unsafe { mpc_oneof(chars.as_ptr()) }, b"char\0")
+}
+
+/// Match none of the bytes of `chars`
+pub fn noneof<'a>(chars: &str) -> Combinator<'a>
+{
+	let chars = CString::new(chars).unwrap();
+	leaf(unsafe { mpc_noneof(chars.as_ptr()) }, b"char\0")
+}
+
+/// Match the string `s`
+pub fn string<'a>(s: &str) -> Combinator<'a>
+{
+	let s = CString::new(s).unwrap();
+	leaf(unsafe { mpc_string(s.as_ptr()) }, b"string\0")
+}
+
+/// Match the regular expression `re`, with mpc's syntax
+pub fn regex<'a>(re: &str) -> Combinator<'a>
+{
+	let re = CString::new(re).unwrap();
+	leaf(unsafe { mpc_re(re.as_ptr()) }, b"regex\0")
+}
+
+/// Match a byte for which `f` returns true. Bytes are passed one at
+/// a time, so a multi-byte UTF-8 character takes several of them
+pub fn satisfy<'a, F>(f: F) -> Combinator<'a>
+	where F: Fn(u8) -> bool + 'static
+{
+	// the callback is kept by the returned combinator
+	let (raw, callback) = unsafe { callback::satisfy_with(f) };
+	leaf(raw, b"char\0").with(&mut Owned { callbacks: vec![callback], ..Owned::default() })
+}
+
+/// Succeed without consuming input when `f` returns true for the
+/// last and the next byte, which are 0 at the start and at the
+/// end of input. Produces no node
+pub fn anchor<'a, F>(f: F) -> Combinator<'a>
+	where F: Fn(u8, u8) -> bool + 'static
+{
+	// the callback is kept by the returned combinator
+	let (raw, callback) = unsafe { callback::anchor_with(f) };
+	unsafe { Combinator::from_raw(raw) }.with(&mut Owned { callbacks: vec![callback], ..Owned::default() })
+}
+
+/// Match a single digit
+pub fn digit<'a>() -> Combinator<'a> { leaf(unsafe { mpc_digit() }, b"digit\0") }
+/// Match one or more digits
+pub fn digits<'a>() -> Combinator<'a> { leaf(unsafe { mpc_digits() }, b"digits\0") }
+/// Match one or more hex digits
+pub fn hexdigits<'a>() -> Combinator<'a> { leaf(unsafe { mpc_hexdigits() }, b"hexdigits\0") }
+/// Match one or more octal digits
+pub fn octdigits<'a>() -> Combinator<'a> { leaf(unsafe { mpc_octdigits() }, b"octdigits\0") }
+/// Match a floating point number
+pub fn real<'a>() -> Combinator<'a> { leaf(unsafe { mpc_real() }, b"real\0") }
+/// Match an alphabet character
+pub fn alpha<'a>() -> Combinator<'a> { leaf(unsafe { mpc_alpha() }, b"alpha\0") }
+/// Match a lowercase character
+pub fn lower<'a>() -> Combinator<'a> { leaf(unsafe { mpc_lower() }, b"lower\0") }
+/// Match an uppercase character
+pub fn upper<'a>() -> Combinator<'a> { leaf(unsafe { mpc_upper() }, b"upper\0") }
+/// Match an alphabet character, an underscore or a digit
+pub fn alphanum<'a>() -> Combinator<'a> { leaf(unsafe { mpc_alphanum() }, b"alphanum\0") }
+/// Match a valid C identifier
+pub fn ident<'a>() -> Combinator<'a> { leaf(unsafe { mpc_ident() }, b"ident\0") }
+/// Match a whitespace character
+pub fn whitespace<'a>() -> Combinator<'a> { leaf(unsafe { mpc_whitespace() }, b"whitespace\0") }
+/// Match a newline
+pub fn newline<'a>() -> Combinator<'a> { leaf(unsafe { mpc_newline() }, b"newline\0") }
+/// Match an escape sequence
+pub fn escape<'a>() -> Combinator<'a> { leaf(unsafe { mpc_escape() }, b"escape\0") }
+/// Match a string literal including its quotes
+pub fn string_lit<'a>() -> Combinator<'a> { leaf(unsafe { mpc_string_lit() }, b"string_lit\0") }
+
+/// A set of named parsers, which can refer to each other and to
+/// themselves. All of them are freed together when dropped, unless
+/// rules of another `Rules` still refer to them. Two `Rules` whose
+/// rules refer to each other are never freed
+///
+/// ```rust
+/// # extern crate mpc;
+/// use mpc::combinators as p;
+/// # fn main() {
+/// let rules = p::Rules::new();
+/// let list = rules.rule("list");
+/// rules.define(list, p::digits().or(list).tok().many().between("(", ")"));
+///
+/// let tree = list.parse("<input>", "(1 (2 3) ())").unwrap();
+/// # }
+/// ```
+pub struct Rules
+{
+	pub(crate) set: Rc<RuleSet>,
+}
+
+/// The parsers of `Rules`, shared with the definitions referring to them
+pub(crate) struct RuleSet
+{
+	pub(crate) parsers: RefCell<Vec<Parser>>,
+	/// names of the rules and what their definitions point to
+	pub(crate) owned: RefCell<Owned>,
+}
+
+/// A named parser of `Rules`, usable as a `Combinator`
+#[derive(Clone, Copy)]
+pub struct Rule<'a>
+{
+	raw: parser_ptr,
+	name: *const c_char,
+	rules: &'a Rules,
+	lifetime: Invariant<'a>,
+}
+
+impl Rules
+{
+	/// Create an empty set of rules
+	pub fn new() -> Rules
+	{
+		Rules
+		{
+			set: Rc::new(RuleSet
+			{
+				parsers: RefCell::new(Vec::new()),
+				owned: RefCell::new(Owned::default()),
+			}),
+		}
+	}
+
+	/// Create an undefined rule called `name`
+	pub fn rule(&self, name: &str) -> Rule<'_>
+	{
+		let parser = Parser::new(name);
+		let name = CString::new(name).unwrap();
+		let rule = Rule
+		{
+			raw: parser.as_ptr(),
+			name: name.as_ptr(),
+			rules: self,
+			lifetime: PhantomData,
+		};
+
+		self.set.parsers.borrow_mut().push(parser);
+		self.set.owned.borrow_mut().tags.push(name);
+		rule
+	}
+
+	/// Define `rule` as `combinator`, replacing any previous definition.
+	/// Rules of other `Rules` it refers to are kept alive until these are
+	/// dropped, but can't be used once their `Rules` is gone:
+	///
+	/// ```rust,compile_fail
+	/// # extern crate mpc;
+	/// use mpc::combinators::{Combinator, Rules};
+	/// # fn main() {
+	/// let a = Rules::new();
+	/// let ra = a.rule("a");
+	/// {
+	///     let b = Rules::new();
+	///     let rb = b.rule("b");
+	///     a.define(ra, Combinator::from(rb));
+	/// }
+	/// let _ = ra.parse("<input>", "");
+	/// # }
+	/// ```
+	pub fn define<'a>(&'a self, rule: Rule<'a>, combinator: Combinator<'a>)
+	{
+		let (raw, owned) = combinator.into_parts();
+		unsafe
+		{
+			mpc_undefine(rule.raw);
+			mpc_define(rule.raw, raw);
+		}
+		self.keep(owned);
+	}
+
+	/// Keep what a definition points to, but not these rules themselves
+	/// so that referring to them doesn't make a cycle
+	pub(crate) fn keep(&self, mut owned: Owned)
+	{
+		owned.rules.retain(|r| !Rc::ptr_eq(r, &self.set));
+		self.set.owned.borrow_mut().append(&mut owned);
+	}
+}
+
+impl Default for Rules
+{
+	fn default() -> Rules
+	{
+		Rules::new()
+	}
+}
+
+impl Drop for RuleSet
+{
+	fn drop(&mut self)
+	{
+		// before the rules of other sets it refers to are released
+		parser::cleanup(mem::take(self.parsers.get_mut()));
+	}
+}
+
+impl<'a> Rule<'a>
+{
+	/// Pointer to the underlying parser
+	pub fn as_ptr(&self) -> parser_ptr
+	{
+		self.raw
+	}
+
+	/// Parse `input` using this rule, see `Combinator::parse`
+	pub fn parse(self, filename: &str, input: &str) -> Result<ParseTree, ParseError>
+	{
+		Combinator::from(self).parse(filename, input)
+	}
+}
+
+impl<'a> From<Rule<'a>> for Combinator<'a>
+{
+	/// Refer to `rule`, its nodes are tagged with its name
+	/// the same way rules of a grammar are
+	fn from(rule: Rule<'a>) -> Combinator<'a>
+	{
+		let combinator = unsafe { Combinator::from_raw(mpca_state(mpca_root(mpca_add_tag(rule.raw, rule.name)))) };
+		combinator.with(&mut Owned { rules: vec![rule.rules.set.clone()], ..Owned::default() })
+	}
+}
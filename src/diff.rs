@@ -0,0 +1,343 @@
+//! Contains a structural diff of two `Ast`s and its renderer
+#![allow(dead_code)]
+
+use ast::Ast;
+use colors::*;
+use std::fmt;
+
+/// A difference between two Asts found by `diff`. Paths are made of the
+/// first component of each tag, indexed among the siblings sharing it,
+/// e.g. `>/sentence[1]/word[3]`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AstChange
+{
+	/// A node only existing in the new tree
+	Inserted
+	{
+		/// Path of the node in the new tree
+		path: String,
+		/// The inserted node
+		node: Ast,
+	},
+	/// A node only existing in the old tree
+	Removed
+	{
+		/// Path of the node in the old tree
+		path: String,
+		/// The removed node
+		node: Ast,
+	},
+	/// A node whose tag changed
+	Retagged
+	{
+		/// Path of the node in the new tree
+		path: String,
+		/// Tag in the old tree
+		old: String,
+		/// Tag in the new tree
+		new: String,
+	},
+	/// A node whose contents changed
+	Contents
+	{
+		/// Path of the node in the new tree
+		path: String,
+		/// Contents in the old tree
+		old: String,
+		/// Contents in the new tree
+		new: String,
+	},
+	/// A leaf whose location changed
+	Moved
+	{
+		/// Path of the node in the new tree
+		path: String,
+		/// Row and column in the old tree
+		old: (usize, usize),
+		/// Row and column in the new tree
+		new: (usize, usize),
+	},
+}
+
+impl AstChange
+{
+	/// Path of the changed node
+	pub fn path(&self) -> &str
+	{
+		match *self
+		{
+			AstChange::Inserted { ref path, .. } |
+			AstChange::Removed { ref path, .. } |
+			AstChange::Retagged { ref path, .. } |
+			AstChange::Contents { ref path, .. } |
+			AstChange::Moved { ref path, .. } => path,
+		}
+	}
+}
+
+/// Options of `diff_with`, locations of leaves are compared by default
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiffOptions
+{
+	/// Report leaves whose row or column changed
+	pub positions: bool,
+}
+
+impl DiffOptions
+{
+	/// Enable or disable comparing locations, disabled it
+	/// matches the behaviour of `mpc_ast_eq`
+	pub fn positions(mut self, positions: bool) -> DiffOptions
+	{
+		self.positions = positions;
+		self
+	}
+}
+
+impl Default for DiffOptions
+{
+	fn default() -> DiffOptions
+	{
+		DiffOptions { positions: true }
+	}
+}
+
+/// Differences turning `old` into `new`, in pre-order of the new tree
+pub fn diff(old: &Ast, new: &Ast) -> Vec<AstChange>
+{
+	diff_with(old, new, DiffOptions::default())
+}
+
+/// Differences turning `old` into `new` using `options`
+pub fn diff_with(old: &Ast, new: &Ast, options: DiffOptions) -> Vec<AstChange>
+{
+	let mut changes = Vec::new();
+	compare(old, new, name(&new.tag).to_owned(), name(&old.tag).to_owned(), &options, &mut changes);
+	changes
+}
+
+/// First component of a folded tag, used in paths
+fn name(tag: &str) -> &str
+{
+	tag.split('|').next().unwrap_or(tag)
+}
+
+/// Paths of `children` below `parent`
+fn paths(parent: &str, children: &[Ast]) -> Vec<String>
+{
+	let mut seen: Vec<&str> = Vec::new();
+	children.iter().map(|child|
+	{
+		let name = name(&child.tag);
+		let index = seen.iter().filter(|n| **n == name).count();
+		seen.push(name);
+		format!("{}/{}[{}]", parent, name, index)
+	}).collect()
+}
+
+/// Pending work of `compare`, kept on a stack instead of recursing so
+/// that deep trees don't overflow the call stack
+enum Step<'a>
+{
+	/// Compare a pair of matched nodes, with the paths of the new and old node
+	Compare(&'a Ast, &'a Ast, String, String),
+	/// Report a change found while comparing their parent
+	Report(AstChange),
+}
+
+/// Compare a pair of matched nodes, `path` being that of the new
+/// node and `old_path` that of the old one
+fn compare(old: &Ast, new: &Ast, path: String, old_path: String, options: &DiffOptions, changes: &mut Vec<AstChange>)
+{
+	let mut stack = vec![Step::Compare(old, new, path, old_path)];
+	while let Some(step) = stack.pop()
+	{
+		let (old, new, path, old_path) = match step
+		{
+			Step::Compare(old, new, path, old_path) => (old, new, path, old_path),
+			Step::Report(change) =>
+			{
+				changes.push(change);
+				continue;
+			}
+		};
+
+		if old.tag != new.tag
+		{
+			changes.push(AstChange::Retagged { path: path.clone(), old: old.tag.clone(), new: new.tag.clone() });
+		}
+		if old.contents != new.contents
+		{
+			changes.push(AstChange::Contents { path: path.clone(), old: old.contents.clone(), new: new.contents.clone() });
+		}
+		if options.positions && old.children.is_empty() && new.children.is_empty()
+			&& (old.row, old.column) != (new.row, new.column)
+		{
+			changes.push(AstChange::Moved { path: path.clone(), old: (old.row, old.column), new: (new.row, new.column) });
+		}
+
+		let old_paths = paths(&old_path, &old.children);
+		let new_paths = paths(&path, &new.children);
+
+		// matching children are paired by their longest common
+		// subsequence, unmatched ones in between are compared pairwise.
+		// Steps are gathered in order, then stacked in reverse
+		let mut steps = Vec::new();
+		let matches = common_nodes(&old.children, &new.children);
+		let (mut i, mut j) = (0, 0);
+		for &(mi, mj) in matches.iter().chain(Some((old.children.len(), new.children.len())).iter())
+		{
+			while i < mi && j < mj
+			{
+				steps.push(Step::Compare(&old.children[i], &new.children[j], new_paths[j].clone(), old_paths[i].clone()));
+				i += 1;
+				j += 1;
+			}
+			for (path, node) in old_paths[i..mi].iter().zip(&old.children[i..mi])
+			{
+				steps.push(Step::Report(AstChange::Removed { path: path.clone(), node: node.clone() }));
+			}
+			for (path, node) in new_paths[j..mj].iter().zip(&new.children[j..mj])
+			{
+				steps.push(Step::Report(AstChange::Inserted { path: path.clone(), node: node.clone() }));
+			}
+
+			if mi < old.children.len()
+			{
+				steps.push(Step::Compare(&old.children[mi], &new.children[mj], new_paths[mj].clone(), old_paths[mi].clone()));
+			}
+			i = mi + 1;
+			j = mj + 1;
+		}
+		stack.extend(steps.into_iter().rev());
+	}
+}
+
+/// Indices of the longest common subsequence of `a` and `b` made of matching nodes
+fn common_nodes(a: &[Ast], b: &[Ast]) -> Vec<(usize, usize)>
+{
+	// lengths[i][j] is the length of the LCS of a[i..] and b[j..]
+	let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+	for i in (0..a.len()).rev()
+	{
+		for j in (0..b.len()).rev()
+		{
+			lengths[i][j] =
+				if matching(&a[i], &b[j]) { lengths[i + 1][j + 1] + 1 }
+				else if lengths[i + 1][j] >= lengths[i][j + 1] { lengths[i + 1][j] }
+				else { lengths[i][j + 1] };
+		}
+	}
+
+	let mut matches = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < a.len() && j < b.len()
+	{
+		if matching(&a[i], &b[j])
+		{
+			matches.push((i, j));
+			i += 1;
+			j += 1;
+		}
+		else if lengths[i + 1][j] >= lengths[i][j + 1] { i += 1; }
+		else { j += 1; }
+	}
+	matches
+}
+
+/// Nodes are matched if their tags and contents are equal, so inner
+/// nodes are matched by tag and leaves also by their contents
+fn matching(a: &Ast, b: &Ast) -> bool
+{
+	a.tag == b.tag && a.contents == b.contents
+}
+
+impl fmt::Display for AstChange
+{
+	/// A single plain line, see `DiffReport`
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}", Line { change: self, colors: false })
+	}
+}
+
+/// A list of `AstChange`s rendered one per line, prefixed with `+`
+/// for inserted nodes, `-` for removed ones and `~` for the rest.
+/// Printed using `Display`
+///
+/// ```text
+/// ~ >/sentence[0]/word[1]: 'big' -> 'small'
+/// - >/sentence[0]/word[2]: word|regex 'brown'
+/// ```
+pub struct DiffReport<'a>
+{
+	changes: &'a [AstChange],
+	colors: bool,
+}
+
+impl<'a> DiffReport<'a>
+{
+	/// Create a colored report of `changes`
+	pub fn new(changes: &'a [AstChange]) -> DiffReport<'a>
+	{
+		DiffReport
+		{
+			changes: changes,
+			colors: true,
+		}
+	}
+
+	/// Enable or disable ANSI colors
+	pub fn colors(mut self, colors: bool) -> DiffReport<'a>
+	{
+		self.colors = colors;
+		self
+	}
+}
+
+impl<'a> fmt::Display for DiffReport<'a>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		for change in self.changes
+		{
+			writeln!(f, "{}", Line { change: change, colors: self.colors })?;
+		}
+		Ok(())
+	}
+}
+
+/// A single change written by `DiffReport`, without a newline
+struct Line<'a>
+{
+	change: &'a AstChange,
+	colors: bool,
+}
+
+impl<'a> fmt::Display for Line<'a>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let (sign, color) = match *self.change
+		{
+			AstChange::Inserted { .. } => ('+', GREEN),
+			AstChange::Removed { .. } => ('-', RED),
+			_ => ('~', YELLOW),
+		};
+		write!(f, "{}{} {}{}: ", paint(self.colors, color), sign, self.change.path(), paint(self.colors, RESET))?;
+
+		match *self.change
+		{
+			AstChange::Inserted { ref node, .. } |
+			AstChange::Removed { ref node, .. } =>
+				if node.children.is_empty() { write!(f, "{} '{}'", node.tag, node.contents) }
+				else { write!(f, "{} ({} children)", node.tag, node.children.len()) },
+			AstChange::Retagged { ref old, ref new, .. } =>
+				write!(f, "{} -> {}", old, new),
+			AstChange::Contents { ref old, ref new, .. } =>
+				write!(f, "'{}' -> '{}'", old, new),
+			AstChange::Moved { old, new, .. } =>
+				write!(f, "moved {}:{} -> {}:{}", old.0 + 1, old.1 + 1, new.0 + 1, new.1 + 1),
+		}
+	}
+}
@@ -0,0 +1,194 @@
+//! Contains an owned error type to which `mpc_err_t` is mapped
+#![allow(dead_code)]
+
+use colors::*;
+use mpc_c::*;
+use mpc_c_types::*;
+use report::Report;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::slice;
+
+/// An error returned by a failed parse
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError
+{
+	/// Name of the parsed file, `<input>` when there is none
+	pub filename: String,
+	/// Failure message, mpc uses it instead of `expected` for errors
+	/// not caused by the input, like unreadable files
+	pub failure: Option<String>,
+	/// Items that were expected at the error's position
+	pub expected: Vec<String>,
+	/// Character found at the error's position, `'\0'` at the end of input
+	pub received: char,
+	/// Number of the row where the error occurred
+	pub row: usize,
+	/// Number of the column where the error occurred
+	pub column: usize,
+	/// Total position in the file where the error occurred
+	pub position: usize,
+}
+
+impl ParseError
+{
+	/// Create a `ParseError` from a raw `mpc_err_t`, the raw error is deleted
+	///
+	/// # Safety
+	///
+	/// `err` must be a valid error allocated by mpc, such as one returned
+	/// by `glue::parse`. It must not be used or deleted afterwards
+	pub unsafe fn from_raw(err: *mut mpc_err_t) -> ParseError
+	{
+		let expected =
+			if (*err).expected_num > 0
+			{
+				slice::from_raw_parts((*err).expected, (*err).expected_num as usize)
+					.iter()
+					.map(|e| str_c!(*e))
+					.collect()
+			}
+			else { Vec::new() };
+
+		let error = ParseError
+		{
+			filename: dfs!(err, filename),
+			failure:
+				if (*err).failure.is_null() { None }
+				else { Some(dfs!(err, failure)) },
+			expected: expected,
+			received: (*err).recieved as u8 as char,
+			row: dfu!(err, state.row),
+			column: dfu!(err, state.col),
+			position: dfu!(err, state.pos),
+		};
+
+		mpc_err_delete(err);
+		error
+	}
+
+	/// An error not caused by the input, mpc reports those with a failure
+	/// message alone
+	pub fn failure(filename: &str, failure: String) -> ParseError
+	{
+		ParseError
+		{
+			filename: filename.to_owned(),
+			failure: Some(failure),
+			expected: Vec::new(),
+			received: '\0',
+			row: 0,
+			column: 0,
+			position: 0,
+		}
+	}
+
+	/// Annotated report of this error, `input` being the parsed text
+	pub fn report<'a>(&'a self, input: &'a str) -> Report<'a>
+	{
+		Report::new(self, input)
+	}
+}
+
+impl fmt::Display for ParseError
+{
+	/// Same output as `mpc_err_string`
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		if let Some(ref failure) = self.failure
+		{
+			return writeln!(f, "{}{}{}:{}error{}: {}", YELLOW, self.filename, RESET, RED, RESET, failure);
+		}
+
+		writeln!(f, "{}{}{}:{}{}{}:{}{}{}:{}error{}: expected {} at {}",
+			YELLOW, self.filename, RESET,
+			GREEN, self.row + 1, RESET,
+			GREEN, self.column + 1, RESET,
+			RED, RESET,
+			expected_list(&self.expected),
+			char_unescape(self.received)
+		)
+	}
+}
+
+impl Error for ParseError {}
+
+/// An error returned when parsing input read from a file or a reader
+#[derive(Debug)]
+pub enum ReadError
+{
+	/// The input could not be opened or read
+	Io(io::Error),
+	/// The input was read, but did not parse
+	Parse(ParseError),
+}
+
+impl fmt::Display for ReadError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			ReadError::Io(ref e) => write!(f, "{}", e),
+			ReadError::Parse(ref e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error for ReadError
+{
+	fn source(&self) -> Option<&(dyn Error + 'static)>
+	{
+		match *self
+		{
+			ReadError::Io(ref e) => Some(e),
+			ReadError::Parse(ref e) => Some(e),
+		}
+	}
+}
+
+impl From<io::Error> for ReadError
+{
+	fn from(e: io::Error) -> ReadError
+	{
+		ReadError::Io(e)
+	}
+}
+
+impl From<ParseError> for ReadError
+{
+	fn from(e: ParseError) -> ReadError
+	{
+		ReadError::Parse(e)
+	}
+}
+
+/// Join expected items the way mpc does: `a, b or c`
+pub fn expected_list(expected: &[String]) -> String
+{
+	match expected.len()
+	{
+		0 => "ERROR: NOTHING EXPECTED".to_owned(),
+		1 => expected[0].clone(),
+		n => format!("{} or {}", expected[..n - 1].join(", "), expected[n - 1]),
+	}
+}
+
+/// Describe a received character the way mpc does
+pub fn char_unescape(c: char) -> String
+{
+	match c
+	{
+		'\x07' => "bell".to_owned(),
+		'\x08' => "backspace".to_owned(),
+		'\x0c' => "formfeed".to_owned(),
+		'\r' => "carriage return".to_owned(),
+		'\x0b' => "vertical tab".to_owned(),
+		'\0' => "end of input".to_owned(),
+		'\n' => "newline".to_owned(),
+		'\t' => "tab".to_owned(),
+		' ' => "space".to_owned(),
+		c => format!("'{}'", c),
+	}
+}
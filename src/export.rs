@@ -0,0 +1,134 @@
+//! Contains exporters of `Ast` to S-expressions and Graphviz DOT
+#![allow(dead_code)]
+
+use ast::Ast;
+use std::fmt::{self, Write};
+
+impl Ast
+{
+	/// Write this Ast as an S-expression, one node per line. Leaves
+	/// are written as `(tag "contents")`, other nodes as `(tag children...)`:
+	///
+	/// ```text
+	/// (>
+	///   (pair|>
+	///     (char "(")
+	///     (word|regex "a")
+	///     (char ")")))
+	/// ```
+	pub fn write_sexpr<W: Write + ?Sized>(&self, w: &mut W) -> fmt::Result
+	{
+		// explicit stack so that deep trees can't overflow it,
+		// holds nodes along with the index of their next child
+		let mut stack: Vec<(&Ast, usize)> = Vec::new();
+		let mut node = self;
+
+		loop
+		{
+			if !stack.is_empty()
+			{
+				w.write_char('\n')?;
+				for _ in 0..stack.len() { w.write_str("  ")?; }
+			}
+
+			if node.children.is_empty()
+			{
+				write!(w, "({} {:?})", node.tag, node.contents)?;
+			}
+			else
+			{
+				write!(w, "({}", node.tag)?;
+				stack.push((node, 0));
+			}
+
+			// find the next node to write, closing finished ones
+			loop
+			{
+				let (parent, next) = match stack.last_mut()
+				{
+					Some(top) =>
+					{
+						let current = *top;
+						top.1 += 1;
+						current
+					},
+					None => return Ok(()),
+				};
+
+				if let Some(child) = parent.children.get(next)
+				{
+					node = child;
+					break;
+				}
+
+				w.write_char(')')?;
+				stack.pop();
+			}
+		}
+	}
+
+	/// This Ast as an S-expression, see `Ast::write_sexpr`
+	pub fn to_sexpr(&self) -> String
+	{
+		let mut out = String::new();
+		let _ = self.write_sexpr(&mut out);
+		out
+	}
+
+	/// Write this Ast as a Graphviz DOT digraph. Nodes are labeled with
+	/// their tag, leaves also with their contents
+	pub fn write_dot<W: Write + ?Sized>(&self, w: &mut W) -> fmt::Result
+	{
+		w.write_str("digraph ast {\n")?;
+
+		// ids of the ancestors of the current node, by depth
+		let mut parents: Vec<usize> = Vec::new();
+		for (id, (depth, node)) in self.iter_preorder().enumerate()
+		{
+			parents.truncate(depth);
+
+			if node.children.is_empty()
+			{
+				writeln!(w, "  n{} [shape=box, label=\"{}\\n{}\"];",
+					id, dot_escape(&node.tag), dot_escape(&format!("{:?}", node.contents)))?;
+			}
+			else
+			{
+				writeln!(w, "  n{} [label=\"{}\"];", id, dot_escape(&node.tag))?;
+			}
+
+			if let Some(parent) = parents.last()
+			{
+				writeln!(w, "  n{} -> n{};", parent, id)?;
+			}
+			parents.push(id);
+		}
+
+		w.write_str("}\n")
+	}
+
+	/// This Ast as a Graphviz DOT digraph, see `Ast::write_dot`
+	pub fn to_dot(&self) -> String
+	{
+		let mut out = String::new();
+		let _ = self.write_dot(&mut out);
+		out
+	}
+}
+
+/// Escape `text` for use inside a quoted DOT string
+fn dot_escape(text: &str) -> String
+{
+	let mut out = String::with_capacity(text.len());
+	for c in text.chars()
+	{
+		match c
+		{
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			c => out.push(c),
+		}
+	}
+	out
+}
@@ -1,4 +1,7 @@
+#define _GNU_SOURCE
+
 #include <stdarg.h>
+#include <stdio.h>
 #include <stdlib.h>
 
 #include "mpc.h"
@@ -24,3 +27,114 @@ parse_result glue_parse(const char* filename, const char* string, mpc_parser_t*
 	else
 		return (parse_result){ ok: NULL, err: r.error, res: err};
 }
+
+parse_result glue_nparse(const char* filename, const char* string, size_t length, mpc_parser_t* p)
+{
+	mpc_result_t r;
+	if (mpc_nparse(filename, string, length, p, &r))
+		return (parse_result){ ok: r.output, err: NULL, res: ok };
+	else
+		return (parse_result){ ok: NULL, err: r.error, res: err};
+}
+
+mpc_parser_t* glue_leaf(mpc_parser_t* p, const char* tag)
+{
+	return mpca_state(mpca_tag(mpc_apply(p, mpcf_str_ast), tag));
+}
+
+mpc_parser_t* glue_tok(mpc_parser_t* p)
+{
+	return mpc_and(2, mpcf_fst_free, p, mpc_whitespaces(), (mpc_dtor_t)mpc_ast_delete);
+}
+
+parse_result glue_parse_file(const char* filename, FILE* f, mpc_parser_t* p)
+{
+	mpc_result_t r;
+	if (mpc_parse_file(filename, f, p, &r))
+		return (parse_result){ ok: r.output, err: NULL, res: ok };
+	else
+		return (parse_result){ ok: NULL, err: r.error, res: err};
+}
+
+parse_result glue_parse_pipe(const char* filename, FILE* f, mpc_parser_t* p)
+{
+	mpc_result_t r;
+	if (mpc_parse_pipe(filename, f, p, &r))
+		return (parse_result){ ok: r.output, err: NULL, res: ok };
+	else
+		return (parse_result){ ok: NULL, err: r.error, res: err};
+}
+
+typedef long (*glue_read_t)(void* x, char* buf, size_t size);
+
+typedef struct
+{
+	glue_read_t f;
+	void* x;
+} glue_reader_t;
+
+#if defined(__GLIBC__)
+
+static ssize_t glue_reader_read(void* cookie, char* buf, size_t size)
+{
+	glue_reader_t* r = cookie;
+	return r->f(r->x, buf, size);
+}
+
+static int glue_reader_close(void* cookie)
+{
+	free(cookie);
+	return 0;
+}
+
+FILE* glue_reader(glue_read_t f, void* x)
+{
+	glue_reader_t* r = malloc(sizeof(glue_reader_t));
+	cookie_io_functions_t io = { glue_reader_read, NULL, NULL, glue_reader_close };
+	FILE* file;
+
+	r->f = f;
+	r->x = x;
+	file = fopencookie(r, "r", io);
+	if (file == NULL)
+		free(r);
+	return file;
+}
+
+#elif defined(__APPLE__) || defined(__FreeBSD__) || defined(__NetBSD__) || defined(__OpenBSD__)
+
+static int glue_reader_read(void* cookie, char* buf, int size)
+{
+	glue_reader_t* r = cookie;
+	return (int)r->f(r->x, buf, (size_t)size);
+}
+
+static int glue_reader_close(void* cookie)
+{
+	free(cookie);
+	return 0;
+}
+
+FILE* glue_reader(glue_read_t f, void* x)
+{
+	glue_reader_t* r = malloc(sizeof(glue_reader_t));
+	FILE* file;
+
+	r->f = f;
+	r->x = x;
+	file = funopen(r, glue_reader_read, NULL, NULL, glue_reader_close);
+	if (file == NULL)
+		free(r);
+	return file;
+}
+
+#else
+
+FILE* glue_reader(glue_read_t f, void* x)
+{
+	(void)f;
+	(void)x;
+	return NULL;
+}
+
+#endif
@@ -7,7 +7,8 @@
 #![allow(dead_code)]
 
 use mpc_c_types::*;
-use std::os::raw::c_char;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_long, c_void};
 
 
 /// Determines whether compilation was successful
@@ -39,16 +40,81 @@ extern
 {
 	/// The C function which handles parsing
 	pub fn glue_parse(filename: *const c_char, string: *const c_char, p: *mut mpc_parser_t) -> parse_result;
+	/// Parse `length` bytes of `string`, which may contain nul bytes
+	pub fn glue_nparse(filename: *const c_char, string: *const c_char, length: usize, p: *mut mpc_parser_t) -> parse_result;
+	/// Turn the string output of `p` into a leaf tagged `tag` and located
+	/// like the leaves of `mpca_lang` grammars. `tag` is not copied
+	pub fn glue_leaf(p: *mut mpc_parser_t, tag: *const c_char) -> *mut mpc_parser_t;
+	/// Run `p` producing an Ast and skip trailing whitespace
+	pub fn glue_tok(p: *mut mpc_parser_t) -> *mut mpc_parser_t;
+	/// Parse the contents of the seekable stream `f`
+	pub fn glue_parse_file(filename: *const c_char, f: *mut FILE, p: *mut mpc_parser_t) -> parse_result;
+	/// Parse the contents of the stream `f`, buffering only what
+	/// may be backtracked over
+	pub fn glue_parse_pipe(filename: *const c_char, f: *mut FILE, p: *mut mpc_parser_t) -> parse_result;
+	/// Open a read-only stream calling `f` with `x` for data. `f` returns the
+	/// number of bytes written to the buffer, 0 at the end and -1 on error.
+	/// Returns a nullptr if custom streams aren't supported by the platform
+	pub fn glue_reader(f: glue_read_t, x: *mut c_void) -> *mut FILE;
 }
 
-/// Convert parse_result to `Result<*mut mpc_ast_t, *mut mpc_err_t>`. It is more idiomatic
-pub fn parse(filename: *const c_char, string: *const c_char, p: *mut mpc_parser_t) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
-{
-	let res = unsafe { glue_parse(filename, string, p) };
+/// Callback of `glue_reader` filling a buffer of `size` bytes
+pub type glue_read_t = unsafe extern "C" fn(x: *mut c_void, buf: *mut c_char, size: usize) -> c_long;
 
+fn into_result(res: parse_result) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
+{
 	match res.res
 	{
 		res_t::ok => Ok(res.ok),
 		res_t::err => Err(res.err),
 	}
 }
+
+/// Convert parse_result to `Result<*mut mpc_ast_t, *mut mpc_err_t>`. It is more idiomatic.
+/// The strings are borrowed, so they outlive the call
+///
+/// # Safety
+///
+/// `p` must be a valid parser
+pub unsafe fn parse(filename: &CStr, string: &CStr, p: *mut mpc_parser_t) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
+{
+	into_result(glue_parse(filename.as_ptr(), string.as_ptr(), p))
+}
+
+/// Like `parse`, parsing `bytes` which may contain nul bytes
+///
+/// # Safety
+///
+/// `p` must be a valid parser
+pub unsafe fn nparse(filename: &CStr, bytes: &[u8], p: *mut mpc_parser_t) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
+{
+	into_result(glue_nparse(filename.as_ptr(), bytes.as_ptr() as *const c_char, bytes.len(), p))
+}
+
+/// Like `parse`, reading from the seekable stream `f`
+///
+/// # Safety
+///
+/// `f` must be a stream open for reading and `p` a valid parser
+pub unsafe fn parse_file(filename: &CStr, f: *mut FILE, p: *mut mpc_parser_t) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
+{
+	into_result(glue_parse_file(filename.as_ptr(), f, p))
+}
+
+/// Like `parse`, reading from the stream `f` which needn't be seekable
+///
+/// # Safety
+///
+/// `f` must be a stream open for reading and `p` a valid parser
+pub unsafe fn parse_pipe(filename: &CStr, f: *mut FILE, p: *mut mpc_parser_t) -> Result<*mut mpc_ast_t, *mut mpc_err_t>
+{
+	into_result(glue_parse_pipe(filename.as_ptr(), f, p))
+}
+
+/// `filename` as passed to mpc, which reports it in errors.
+/// Nul bytes are escaped as `\0`, so that any filename is accepted
+pub fn filename(filename: &str) -> CString
+{
+	// no nul byte is left after escaping
+	CString::new(filename.replace('\0', "\\0")).unwrap()
+}
@@ -0,0 +1,462 @@
+//! Contains a builder for `mpca_lang` grammars whose rules
+//! are only known at runtime and the compiled `Language`
+#![allow(dead_code)]
+
+use error::*;
+use glue;
+use mpc_c::*;
+use mpc_c_types::*;
+use parser::Parser;
+use stream::*;
+use tree::ParseTree;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::path::Path;
+
+/// An error encountered while compiling a grammar
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GrammarError
+{
+	/// No main rule was given to the builder
+	NoMain,
+	/// A rule was declared more than once
+	DuplicateRule(String),
+	/// A rule name contains a NUL byte
+	InvalidName(String),
+	/// The grammar contains a NUL byte
+	NulByte,
+	/// The grammar text could not be parsed
+	Syntax
+	{
+		/// Row in the grammar text where the error occurred
+		row: usize,
+		/// Column in the grammar text where the error occurred
+		column: usize,
+		/// Tokens that were expected at that position
+		expected: Vec<String>,
+		/// Failure message, used instead of `expected` by some errors
+		failure: Option<String>,
+	},
+}
+
+impl fmt::Display for GrammarError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			GrammarError::NoMain => write!(f, "no main rule specified"),
+			GrammarError::DuplicateRule(ref name) => write!(f, "rule '{}' declared more than once", name),
+			GrammarError::InvalidName(ref name) => write!(f, "invalid rule name '{}'", name.escape_default()),
+			GrammarError::NulByte => write!(f, "grammar contains a NUL byte"),
+			GrammarError::Syntax { row, column, ref expected, ref failure } =>
+			{
+				write!(f, "invalid grammar at {}:{}: ", row + 1, column + 1)?;
+				match *failure
+				{
+					Some(ref failure) => write!(f, "{}", failure),
+					None => write!(f, "expected {}", expected_list(expected)),
+				}
+			}
+		}
+	}
+}
+
+impl Error for GrammarError {}
+
+/// An error returned when a grammar is compiled and run at once, see `parser!`
+#[derive(Debug)]
+pub enum LanguageError
+{
+	/// The grammar could not be compiled
+	Grammar(GrammarError),
+	/// The input could not be opened or read
+	Io(io::Error),
+	/// The input did not parse
+	Parse(ParseError),
+}
+
+impl fmt::Display for LanguageError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			LanguageError::Grammar(ref e) => write!(f, "{}", e),
+			LanguageError::Io(ref e) => write!(f, "{}", e),
+			LanguageError::Parse(ref e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl Error for LanguageError
+{
+	fn source(&self) -> Option<&(dyn Error + 'static)>
+	{
+		match *self
+		{
+			LanguageError::Grammar(ref e) => Some(e),
+			LanguageError::Io(ref e) => Some(e),
+			LanguageError::Parse(ref e) => Some(e),
+		}
+	}
+}
+
+impl From<GrammarError> for LanguageError
+{
+	fn from(e: GrammarError) -> LanguageError
+	{
+		LanguageError::Grammar(e)
+	}
+}
+
+impl From<ParseError> for LanguageError
+{
+	fn from(e: ParseError) -> LanguageError
+	{
+		LanguageError::Parse(e)
+	}
+}
+
+impl From<ReadError> for LanguageError
+{
+	fn from(e: ReadError) -> LanguageError
+	{
+		match e
+		{
+			ReadError::Io(e) => LanguageError::Io(e),
+			ReadError::Parse(e) => LanguageError::Parse(e),
+		}
+	}
+}
+
+/// Builder for a `Language` from a `mpca_lang` grammar
+///
+/// # Usage
+///
+/// ```rust
+/// # extern crate mpc;
+/// # use mpc::grammar::Grammar;
+/// # fn main() {
+/// let language = Grammar::new(concat!(
+///         "word : /[a-zA-Z0-9]+/;                         \n",
+///         "punct: '.' | '!' | ',' | ';' | '?' | '-' | ':';\n",
+///         "sentence: <word>+ <punct>;                     \n",
+///         "paragraph: <sentence>+;                        \n"))
+///     .rule("word")
+///     .rule("punct")
+///     .rule("sentence")
+///     .main("paragraph")
+///     .build()
+///     .unwrap();
+/// # let _ = language;
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Grammar
+{
+	source: String,
+	main: Option<String>,
+	rules: Vec<String>,
+}
+
+impl Grammar
+{
+	/// Start building a language from grammar `source`
+	pub fn new(source: &str) -> Grammar
+	{
+		Grammar
+		{
+			source: source.to_owned(),
+			main: None,
+			rules: Vec::new(),
+		}
+	}
+
+	/// Declare a rule used by the grammar
+	pub fn rule(mut self, name: &str) -> Grammar
+	{
+		self.rules.push(name.to_owned());
+		self
+	}
+
+	/// Declare the rule parsing starts from
+	pub fn main(mut self, name: &str) -> Grammar
+	{
+		self.main = Some(name.to_owned());
+		self
+	}
+
+	/// Compile the grammar
+	pub fn build(self) -> Result<Language, GrammarError>
+	{
+		let main = match self.main
+		{
+			Some(main) => main,
+			None => return Err(GrammarError::NoMain),
+		};
+
+		let mut names: Vec<String> = vec![main];
+		for rule in self.rules
+		{
+			if names.contains(&rule)
+			{
+				return Err(GrammarError::DuplicateRule(rule));
+			}
+			names.push(rule);
+		}
+
+		let mut parsers: Vec<Parser> = Vec::new();
+		for name in &names
+		{
+			if name.contains('\0')
+			{
+				return Err(GrammarError::InvalidName(name.clone()));
+			}
+			parsers.push(Parser::new(name));
+		}
+
+		let language = Language
+		{
+			parsers: parsers,
+			names: names,
+		};
+
+		let source = match CString::new(self.source)
+		{
+			Ok(source) => source,
+			Err(_) => return Err(GrammarError::NulByte),
+		};
+
+		let mut raw: Vec<parser_ptr> = language.parsers.iter().map(Parser::as_ptr).collect();
+		unsafe
+		{
+			let err = mpca_lang_array(
+				mpca_lang_type::MPCA_LANG_DEFAULT,
+				source.as_ptr(),
+				raw.len() as i32,
+				raw.as_mut_ptr()
+			);
+
+			if !err.is_null()
+			{
+				let error = ParseError::from_raw(err);
+				let syntax = GrammarError::Syntax
+				{
+					row: error.row,
+					column: error.column,
+					expected: error.expected,
+					failure: error.failure,
+				};
+				return Err(syntax);
+			}
+		}
+
+		Ok(language)
+	}
+}
+
+/// A compiled grammar. Owns parsers for all of its rules and
+/// frees them when dropped
+pub struct Language
+{
+	/// the main rule is always first
+	parsers: Vec<Parser>,
+	names: Vec<String>,
+}
+
+impl Language
+{
+	/// The parser of the main rule
+	pub fn main(&self) -> &Parser
+	{
+		&self.parsers[0]
+	}
+
+	/// Names of all rules, the main rule being first
+	pub fn rules(&self) -> &[String]
+	{
+		&self.names
+	}
+
+	/// The rule called `name`, to start parsing from it instead of the
+	/// main rule. `None` if the grammar has no such rule
+	///
+	/// ```rust
+	/// # extern crate mpc;
+	/// # use mpc::grammar::Grammar;
+	/// # fn main() {
+	/// let language = Grammar::new("word : /[a-z]+/; sentence : <word>+ '.';")
+	///     .main("sentence")
+	///     .rule("word")
+	///     .build()
+	///     .unwrap();
+	///
+	/// assert!(language.rule("word").unwrap().parse_str("banana").is_ok());
+	/// assert!(language.rule("verb").is_none());
+	/// # }
+	/// ```
+	pub fn rule(&self, name: &str) -> Option<Rule<'_>>
+	{
+		self.names.iter()
+			.position(|n| n == name)
+			.map(|index| Rule { language: self, index: index })
+	}
+
+	/// The main rule
+	pub fn main_rule(&self) -> Rule<'_>
+	{
+		Rule { language: self, index: 0 }
+	}
+
+	/// Parse `input` using the main rule, `filename` is used in error messages
+	pub fn parse(&self, filename: &str, input: &str) -> Result<ParseTree, ParseError>
+	{
+		self.main_rule().parse(filename, input)
+	}
+
+	/// Parse `input` using the main rule, errors refer to it as `<input>`
+	pub fn parse_str(&self, input: &str) -> Result<ParseTree, ParseError>
+	{
+		self.main_rule().parse_str(input)
+	}
+
+	/// Parse `input` using the main rule, see `Rule::parse_bytes`
+	pub fn parse_bytes(&self, filename: &str, input: &[u8]) -> Result<ParseTree, ParseError>
+	{
+		self.main_rule().parse_bytes(filename, input)
+	}
+
+	/// Parse the file at `path` using the main rule, see `Rule::parse_file`
+	pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<ParseTree, ReadError>
+	{
+		self.main_rule().parse_file(path)
+	}
+
+	/// Parse everything read from `reader` using the main rule, see `Rule::parse_reader`
+	pub fn parse_reader<R: Read>(&self, filename: &str, reader: R) -> Result<ParseTree, ReadError>
+	{
+		self.main_rule().parse_reader(filename, reader)
+	}
+
+	/// Give up ownership of the parsers, the main rule being first.
+	/// The parsers are leaked unless cleaned up manually
+	pub fn into_raw(mut self) -> Vec<parser_ptr>
+	{
+		mem::take(&mut self.parsers)
+			.into_iter()
+			.map(Parser::into_raw)
+			.collect()
+	}
+}
+
+/// A rule of a `Language` used as the entry point of parsing,
+/// see `Language::rule`
+#[derive(Clone, Copy)]
+pub struct Rule<'a>
+{
+	language: &'a Language,
+	index: usize,
+}
+
+impl<'a> Rule<'a>
+{
+	/// Name of the rule
+	pub fn name(&self) -> &'a str
+	{
+		&self.language.names[self.index]
+	}
+
+	/// The parser of the rule
+	pub fn parser(&self) -> &'a Parser
+	{
+		&self.language.parsers[self.index]
+	}
+
+	/// Parse `input`, `filename` is used in error messages.
+	/// `input` may contain nul bytes, see `parse_bytes`
+	pub fn parse(&self, filename: &str, input: &str) -> Result<ParseTree, ParseError>
+	{
+		self.parse_bytes(filename, input.as_bytes())
+	}
+
+	/// Parse `input`, errors refer to it as `<input>`
+	pub fn parse_str(&self, input: &str) -> Result<ParseTree, ParseError>
+	{
+		self.parse("<input>", input)
+	}
+
+	/// Parse `input`, `filename` is used in error messages.
+	/// The input may be any bytes, nul bytes included, and leaves keep them
+	/// in their contents. Contents may not be valid UTF-8, see
+	/// `ParseTree::try_into_ast`
+	pub fn parse_bytes(&self, filename: &str, input: &[u8]) -> Result<ParseTree, ParseError>
+	{
+		let filename = glue::filename(filename);
+		unsafe
+		{
+			match glue::nparse(&filename, input, self.parser().as_ptr())
+			{
+				Ok(a) => Ok(ParseTree::from_raw(a)),
+				Err(e) => Err(ParseError::from_raw(e)),
+			}
+		}
+	}
+
+	/// Parse the file at `path`. The file is read by mpc
+	/// as it goes rather than loaded into memory first
+	pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<ParseTree, ReadError>
+	{
+		let path = path.as_ref();
+		let stream = Stream::open(path)?;
+		// the path was opened, so it contains no nul byte
+		let filename = CString::new(path.to_string_lossy().into_owned()).unwrap();
+		unsafe
+		{
+			match glue::parse_file(&filename, stream.as_ptr(), self.parser().as_ptr())
+			{
+				Ok(a) => Ok(ParseTree::from_raw(a)),
+				Err(e) => Err(ReadError::Parse(ParseError::from_raw(e))),
+			}
+		}
+	}
+
+	/// Parse everything read from `reader`, `filename` is used in error
+	/// messages. Input is buffered only as far as the parser may backtrack,
+	/// so `reader` can be a socket or a pipe. `reader` must not panic
+	pub fn parse_reader<R: Read>(&self, filename: &str, reader: R) -> Result<ParseTree, ReadError>
+	{
+		let mut stream = ReaderStream::new(reader)?;
+		let filename = glue::filename(filename);
+		let res = unsafe
+		{
+			match glue::parse_pipe(&filename, stream.as_ptr(), self.parser().as_ptr())
+			{
+				Ok(a) => Ok(ParseTree::from_raw(a)),
+				Err(e) => Err(ReadError::Parse(ParseError::from_raw(e))),
+			}
+		};
+		// a failed read looks like the end of input to mpc
+		match stream.take_error()
+		{
+			Some(e) => Err(ReadError::Io(e)),
+			None => res,
+		}
+	}
+}
+
+impl Drop for Language
+{
+	fn drop(&mut self)
+	{
+		// rules refer to each other, so none can be deleted
+		// before all of them are undefined
+		for p in &self.parsers
+		{
+			p.undefine();
+		}
+	}
+}
@@ -4,6 +4,13 @@
 #![feature(inclusive_range_syntax)]
 #![deny(warnings, missing_docs)]
 
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "serde"))]
+extern crate bincode;
+
 pub mod glue;
 pub mod colors;
 
@@ -12,6 +19,21 @@ pub mod macros;
 
 pub mod mpc_c;
 pub mod ast;
+pub mod tree;
+pub mod builder;
+pub mod visit;
+pub mod diff;
+pub mod select;
+pub mod export;
+pub mod serialize;
+pub mod parser;
+pub mod callback;
+pub mod combinators;
+pub mod typed;
+pub mod stream;
+pub mod grammar;
+pub mod error;
+pub mod report;
 pub mod mpc_c_types;
 
 mod test;
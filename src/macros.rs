@@ -1,14 +1,17 @@
 //! Contains some nice macros to help with needed FFI and parser creation
 //! The str_c! and c_str! macros exist because of ownership issues
 
-/// Convert a rustic string (whether `&str` or `String`) to C's `const char*`
+/// Convert a rustic string (whether `&str` or `String`) to an owned `CString`,
+/// panics on a nul byte. The `CString` has to outlive any use of its pointer,
+/// so bind it or borrow it as `&CStr` rather than calling `.as_ptr()` on a temporary
+/// that C keeps
 #[macro_export]
 macro_rules! c_str /* C-ish String */
 {
 	($str:expr) =>
 	{{
 		use std::ffi::CString;
-		CString::new($str).unwrap().as_ptr()
+		CString::new($str).unwrap()
 	}}
 }
 
@@ -99,6 +102,40 @@ macro_rules! g_string /* couldn't help it */
 	}}
 }
 
+/// Build an `Ast` by hand, e.g. to compare it with a parsed one.
+/// Nodes are located at 0, use `builder::AstBuilder` to set locations
+/// # Usage
+///
+/// ```rust
+/// # #[macro_use] extern crate mpc;
+/// # fn main() {
+/// let pair = ast!("pair|>", [
+///     ast!("char" => "("),
+///     ast!("word|regex" => "a"),
+///     ast!("char" => ")"),
+/// ]);
+/// let root = ast!(">", [pair]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ast
+{
+	($tag:expr => $contents:expr) =>
+	{{
+		$crate::builder::AstBuilder::new($tag).contents($contents).build()
+	}};
+	($tag:expr, [$($child:expr),* $(,)*]) =>
+	{{
+		$crate::builder::AstBuilder::new($tag)
+			$( .child($child) )*
+			.build()
+	}};
+	($tag:expr) =>
+	{{
+		$crate::builder::AstBuilder::new($tag).build()
+	}};
+}
+
 /// Create a `mpc_parser_t` using `mpca_lang` grammars
 /// # Usage
 /// There are four possible ways to use this macro. Each
@@ -173,7 +210,6 @@ macro_rules! g_string /* couldn't help it */
 ///
 /// ```rust
 /// # #[macro_use] extern crate mpc;
-/// # #[allow(unused_variables)]
 /// # fn main() {
 /// let my_parser = parser!
 /// {
@@ -185,13 +221,29 @@ macro_rules! g_string /* couldn't help it */
 ///     ]}
 ///     main: paragraph
 ///     parsers: word punct sentence
+/// }.unwrap();
+///
+/// assert!(my_parser.parse_str("Bananas are awesome. What?").is_ok());
+/// assert!(my_parser.rule("sentence").unwrap().parse("sentence.txt", "Bananas!").is_ok());
+///
+/// // a typo in the grammar is an error, not a panic
+/// let broken = parser!
+/// {
+///     grammar:{"word : /[a-z]+/; sentence <word>+;"}
+///     main: sentence
+///     parsers: word
 /// };
+/// assert!(broken.is_err());
 /// # }
 /// ```
-/// For cases 1-3 `parser!` returns `Result<*mut mpc_ast_t, *mut mpc_err_t>`
-/// In case 4 `parser!` returns a vector containing prepared parsers. The vector
-/// is not to be touched by a programmer. Its sole purpose is to be passed to
-/// `run_parser!`
+/// Rules are compiled using `grammar::Grammar`.
+///
+/// For cases 1 to 3 `parser!` returns `Result<tree::ParseTree, grammar::LanguageError>`,
+/// the error being an invalid grammar, an unreadable file or input that didn't
+/// parse. In case 2 the file is read by `grammar::Language::parse_file`.
+/// In case 4 `parser!` returns `Result<grammar::Language, grammar::GrammarError>`.
+/// The `Language` can be run any number of times, from its main rule or any
+/// other, and frees all of its rules when dropped. `run_parser!` runs it as well
 #[macro_export]
 macro_rules! parser
 {
@@ -201,182 +253,160 @@ macro_rules! parser
 	 input: {$input:expr}
 	 main: $top:ident
 	 parsers: $($p:ident)+) =>
-	{{ unsafe {
-		use mpc::glue;
-		use mpc::mpc_c::*;
-		use mpc::mpc_c_types::*;
-		use std::os::raw::c_void;
-
-		let $top = mpc_new(c_str!(stringify!($top)));
-		$
-		(
-			let $p = mpc_new(c_str!(stringify!($p)));
-		)+
-
-		mpca_lang(
-			mpca_lang_type::MPCA_LANG_DEFAULT,
-			c_str!($grammar)
-			$(, $p)+,
-			$top,
-			0 as *mut c_void
-		);
+	{{
+		let language = $crate::grammar::Grammar::new($grammar)
+			.main(stringify!($top))
+			$( .rule(stringify!($p)) )+
+			.build();
 
-		glue::parse(
-			c_str!($filename),
-			c_str!($input),
-			$top,
-		)
-	}}};
+		match language
+		{
+			Ok(language) => language.parse($filename, &$input).map_err($crate::grammar::LanguageError::from),
+			Err(e) => Err($crate::grammar::LanguageError::from(e)),
+		}
+	}};
 	/* read the file myself variant */
 	(grammar: {$grammar:expr}
 	 filename: {$filename:expr}
 	 main: $top:ident
 	 parsers: $($p:ident)+) =>
-	{{ unsafe {
-		use mpc::glue;
-		use mpc::mpc_c::*;
-		use mpc::mpc_c_types::*;
-		use std::io::Read;
-		use std::os::raw::c_void;
-		use std::fs::File;
+	{{
+		let language = $crate::grammar::Grammar::new($grammar)
+			.main(stringify!($top))
+			$( .rule(stringify!($p)) )+
+			.build();
 
-		let mut input = String::new();
-		if let Ok(mut ay) = File::open($filename)
-		{
-			let _ = ay.read_to_string(&mut input);
-		};
-		if input == String::new()
+		match language
 		{
-			panic!();
+			Ok(language) => language.parse_file($filename).map_err($crate::grammar::LanguageError::from),
+			Err(e) => Err($crate::grammar::LanguageError::from(e)),
 		}
-		let $top = mpc_new(c_str!(stringify!($top)));
-		$
-		(
-			let $p = mpc_new(c_str!(stringify!($p)));
-		)+
-
-		mpca_lang(
-			mpca_lang_type::MPCA_LANG_DEFAULT,
-			c_str!($grammar)
-			$(, $p)+,
-			$top,
-			0 as *mut c_void
-		);
-
-		glue::parse(
-			c_str!($filename),
-			c_str!(input),
-			$top,
-		)
-	}}};
+	}};
 	/* no filename variant */
 	(grammar: {$grammar:expr}
 	 input: {$input:expr}
 	 main: $top:ident
 	 parsers: $($p:ident)+) =>
-	{{ unsafe {
-		use mpc::glue;
-		use mpc::mpc_c::*;
-		use mpc::mpc_c_types::*;
-		use std::os::raw::c_void;
-		let $top = mpc_new(c_str!(stringify!($top)));
-		$
-		(
-			let $p = mpc_new(c_str!(stringify!($p)));
-		)+
-
-		mpca_lang(
-			mpca_lang_type::MPCA_LANG_DEFAULT,
-			c_str!($grammar)
-			$(, $p)+,
-			$top,
-			0 as *mut c_void
-		);
+	{{
+		let language = $crate::grammar::Grammar::new($grammar)
+			.main(stringify!($top))
+			$( .rule(stringify!($p)) )+
+			.build();
 
-		glue::parse(
-			c_str!("<input>"),
-			c_str!($input),
-			$top,
-		)
-	}}};
+		match language
+		{
+			Ok(language) => language.parse("<input>", &$input).map_err($crate::grammar::LanguageError::from),
+			Err(e) => Err($crate::grammar::LanguageError::from(e)),
+		}
+	}};
 	/* prepare parsers for later use */
 	(grammar: {$grammar:expr}
 	 main: $top:ident
 	 parsers: $($p:ident)+) =>
-	{{ unsafe {
-		use mpc::mpc_c::*;
-		use mpc::mpc_c_types::*;
-		use std::os::raw::c_void;
-
-		//need just the reference to topmost one,
-		//but other parser have to be kept alive as well
-		//preparsers keeps them alive
-		let mut preparsers: Vec<parser_ptr> = Vec::new();
-		let $top = mpc_new(c_str!(stringify!($top)));
-		$(
-			let $p = mpc_new(c_str!(stringify!($p)));
-		)+
-
-		mpca_lang(
-			mpca_lang_type::MPCA_LANG_DEFAULT,
-			c_str!($grammar)
-			$(, $p)+,
-			$top,
-			0 as *mut c_void
-		);
-
-		preparsers.push($top);
-		$(
-			preparsers.push($p);
-		)+
-		preparsers
-	}}};
+	{{
+		$crate::grammar::Grammar::new($grammar)
+			.main(stringify!($top))
+			$( .rule(stringify!($p)) )+
+			.build()
+	}};
 }
 
-/// Runs a parser prepared with `parser!`
+/// Runs a `grammar::Language` prepared with `parser!`, the same as calling its
+/// `parse`, `parse_str` or `parse_file` methods. Returns
+/// `Result<tree::ParseTree, error::ParseError>`, or `error::ReadError`
+/// when given only a filename.
+///
+/// Parsing starts from the main rule, or from any declared rule given as `rule:`.
+/// An unknown rule is reported as a `ParseError` with a failure message:
+///
+/// ```rust
+/// # #[macro_use] extern crate mpc;
+/// # fn main() {
+/// let language = parser!
+/// {
+///     grammar:{g_string![
+///	         "word : /[a-zA-Z0-9]+/;                         \n"
+///	         "punct: '.' | '!' | ',' | ';' | '?' | '-' | ':';\n"
+///	         "sentence: <word>+ <punct>;                     \n"
+///	         "paragraph: <sentence>+;                        \n"
+///     ]}
+///     main: paragraph
+///     parsers: word punct sentence
+/// }.unwrap();
+///
+/// let result = run_parser!
+/// {
+///     preparsers: language
+///     rule: punct
+///     input: {"?"}
+/// };
+/// assert!(result.is_ok());
+///
+/// let result = run_parser!
+/// {
+///     preparsers: language
+///     rule: verb
+///     input: {"?"}
+/// };
+/// assert_eq!(result.err().unwrap().failure.unwrap(), "no rule named 'verb'");
+/// # }
+/// ```
 #[macro_export]
 macro_rules! run_parser
 {
 	(preparsers: $preparsers:ident
+	 rule: $rule:ident
 	 filename: {$filename:expr}
 	 input: {$input:expr}) =>
 	{{
-		use mpc::glue;
-
-		glue::parse(
-			c_str!($filename),
-			c_str!($input),
-			$preparsers[0]
-		)
+		match $preparsers.rule(stringify!($rule))
+		{
+			Some(rule) => rule.parse(&$filename, &$input),
+			None => Err($crate::error::ParseError::failure(&$filename,
+				format!("no rule named '{}'", stringify!($rule)))),
+		}
 	}};
 	(preparsers: $preparsers:ident
+	 rule: $rule:ident
 	 input: {$input:expr}) =>
 	{{
-		use mpc::glue;
-
-		glue::parse(
-			c_str!("<input>"),
-			c_str!($input),
-			$preparsers[0]
-		)
+		match $preparsers.rule(stringify!($rule))
+		{
+			Some(rule) => rule.parse_str(&$input),
+			None => Err($crate::error::ParseError::failure("<input>",
+				format!("no rule named '{}'", stringify!($rule)))),
+		}
 	}};
 	(preparsers: $preparsers:ident
+	 rule: $rule:ident
 	 filename: {$filename:expr}) =>
 	{{
-		use mpc::glue;
-		use std::fs::File;
-
-		let mut input = String::new();
-		if let Ok(mut ay) = File::open($filename)
+		match $preparsers.rule(stringify!($rule))
 		{
-			ay.read_to_string(&mut input);
-		};
-		if input == String::new()
-			{panic!();}
-		glue::parse(
-			c_str!($filename),
-			c_str!(input),
-			$preparsers[0]
-		)
+			Some(rule) => rule.parse_file($filename),
+			None =>
+			{
+				let path: &::std::path::Path = ::std::convert::AsRef::as_ref(&$filename);
+				Err($crate::error::ReadError::Parse($crate::error::ParseError::failure(
+					&path.to_string_lossy(),
+					format!("no rule named '{}'", stringify!($rule)))))
+			}
+		}
+	}};
+	(preparsers: $preparsers:ident
+	 filename: {$filename:expr}
+	 input: {$input:expr}) =>
+	{{
+		$preparsers.parse(&$filename, &$input)
+	}};
+	(preparsers: $preparsers:ident
+	 input: {$input:expr}) =>
+	{{
+		$preparsers.parse_str(&$input)
+	}};
+	(preparsers: $preparsers:ident
+	 filename: {$filename:expr}) =>
+	{{
+		$preparsers.parse_file($filename)
 	}}
 }
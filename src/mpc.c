@@ -90,6 +90,7 @@ typedef struct {
   mpc_state_t state;
 
   char *string;
+  long length;
   char *buffer;
   FILE *file;
 
@@ -118,7 +119,8 @@ static mpc_input_t *mpc_input_new_string(const char *filename, const char *strin
 
   i->state = mpc_state_new();
 
-  i->string = malloc(strlen(string) + 1);
+  i->length = strlen(string);
+  i->string = malloc(i->length + 1);
   strcpy(i->string, string);
   i->buffer = NULL;
   i->file = NULL;
@@ -147,8 +149,10 @@ static mpc_input_t *mpc_input_new_nstring(const char *filename, const char *stri
 
   i->state = mpc_state_new();
 
+  /* may contain nul bytes, so the length is kept instead of relying on strlen */
+  i->length = length;
   i->string = malloc(length + 1);
-  strncpy(i->string, string, length);
+  memcpy(i->string, string, length);
   i->string[length] = '\0';
   i->buffer = NULL;
   i->file = NULL;
@@ -179,6 +183,7 @@ static mpc_input_t *mpc_input_new_pipe(const char *filename, FILE *pipe) {
   i->state = mpc_state_new();
 
   i->string = NULL;
+  i->length = 0;
   i->buffer = NULL;
   i->file = pipe;
 
@@ -207,6 +212,7 @@ static mpc_input_t *mpc_input_new_file(const char *filename, FILE *file) {
   i->state = mpc_state_new();
 
   i->string = NULL;
+  i->length = 0;
   i->buffer = NULL;
   i->file = file;
 
@@ -372,9 +378,9 @@ static char mpc_input_buffer_get(mpc_input_t *i) {
 }
 
 static int mpc_input_terminated(mpc_input_t *i) {
-  if (i->type == MPC_INPUT_STRING && i->state.pos == (long)strlen(i->string)) { return 1; }
-  if (i->type == MPC_INPUT_FILE && feof(i->file)) { return 1; }
-  if (i->type == MPC_INPUT_PIPE && feof(i->file)) { return 1; }
+  if (i->type == MPC_INPUT_STRING && i->state.pos == i->length) { return 1; }
+  if (i->type == MPC_INPUT_FILE && (feof(i->file) || ferror(i->file))) { return 1; }
+  if (i->type == MPC_INPUT_PIPE && (feof(i->file) || ferror(i->file))) { return 1; }
   return 0;
 }
 
@@ -411,7 +417,7 @@ static char mpc_input_peekc(mpc_input_t *i) {
     case MPC_INPUT_FILE:
 
       c = fgetc(i->file);
-      if (feof(i->file)) { return '\0'; }
+      if ((feof(i->file) || ferror(i->file))) { return '\0'; }
 
       fseek(i->file, -1, SEEK_CUR);
       return c;
@@ -420,7 +426,7 @@ static char mpc_input_peekc(mpc_input_t *i) {
 
       if (!i->buffer) {
         c = getc(i->file);
-        if (feof(i->file)) { return '\0'; }
+        if ((feof(i->file) || ferror(i->file))) { return '\0'; }
         ungetc(c, i->file);
         return c;
       }
@@ -429,7 +435,7 @@ static char mpc_input_peekc(mpc_input_t *i) {
         return mpc_input_buffer_get(i);
       } else {
         c = getc(i->file);
-        if (feof(i->file)) { return '\0'; }
+        if ((feof(i->file) || ferror(i->file))) { return '\0'; }
         ungetc(c, i->file);
         return c;
       }
@@ -507,13 +513,13 @@ static int mpc_input_range(mpc_input_t *i, char c, char d, char **o) {
 static int mpc_input_oneof(mpc_input_t *i, const char *c, char **o) {
   char x = mpc_input_getc(i);
   if (mpc_input_terminated(i)) { return 0; }
-  return strchr(c, x) != 0 ? mpc_input_success(i, x, o) : mpc_input_failure(i, x);
+  return x != '\0' && strchr(c, x) != 0 ? mpc_input_success(i, x, o) : mpc_input_failure(i, x);
 }
 
 static int mpc_input_noneof(mpc_input_t *i, const char *c, char **o) {
   char x = mpc_input_getc(i);
   if (mpc_input_terminated(i)) { return 0; }
-  return strchr(c, x) == 0 ? mpc_input_success(i, x, o) : mpc_input_failure(i, x);
+  return x == '\0' || strchr(c, x) == 0 ? mpc_input_success(i, x, o) : mpc_input_failure(i, x);
 }
 
 static int mpc_input_satisfy(mpc_input_t *i, int(*cond)(char), char **o) {
@@ -546,6 +552,17 @@ static int mpc_input_anchor(mpc_input_t* i, int(*f)(char,char), char **o) {
   return f(i->last, mpc_input_peekc(i));
 }
 
+static int mpc_input_satisfy_to(mpc_input_t *i, int(*cond)(char,void*), void *x, char **o) {
+  char c = mpc_input_getc(i);
+  if (mpc_input_terminated(i)) { return 0; }
+  return cond(c, x) ? mpc_input_success(i, c, o) : mpc_input_failure(i, c);
+}
+
+static int mpc_input_anchor_to(mpc_input_t* i, int(*f)(char,char,void*), void *x, char **o) {
+  *o = NULL;
+  return f(i->last, mpc_input_peekc(i), x);
+}
+
 static mpc_state_t *mpc_input_state_copy(mpc_input_t *i) {
   mpc_state_t *r = mpc_malloc(i, sizeof(mpc_state_t));
   memcpy(r, &i->state, sizeof(mpc_state_t));
@@ -692,6 +709,10 @@ static mpc_err_t *mpc_err_file(const char *filename, const char *failure) {
   return x;
 }
 
+mpc_err_t *mpc_err_failure(const char *filename, const char *failure) {
+  return mpc_err_file(filename, failure);
+}
+
 static void mpc_err_delete_internal(mpc_input_t *i, mpc_err_t *x) {
   int j;
   if (x == NULL) { return; }
@@ -898,10 +919,10 @@ enum {
 typedef struct { char *m; } mpc_pdata_fail_t;
 typedef struct { mpc_ctor_t lf; void *x; } mpc_pdata_lift_t;
 typedef struct { mpc_parser_t *x; char *m; } mpc_pdata_expect_t;
-typedef struct { int(*f)(char,char); } mpc_pdata_anchor_t;
+typedef struct { int(*f)(char,char); int(*g)(char,char,void*); void *x; } mpc_pdata_anchor_t;
 typedef struct { char x; } mpc_pdata_single_t;
 typedef struct { char x; char y; } mpc_pdata_range_t;
-typedef struct { int(*f)(char); } mpc_pdata_satisfy_t;
+typedef struct { int(*f)(char); int(*g)(char,void*); void *x; } mpc_pdata_satisfy_t;
 typedef struct { char *x; } mpc_pdata_string_t;
 typedef struct { mpc_parser_t *x; mpc_apply_t f; } mpc_pdata_apply_t;
 typedef struct { mpc_parser_t *x; mpc_apply_to_t f; void *d; } mpc_pdata_apply_to_t;
@@ -956,10 +977,36 @@ static mpc_val_t *mpcf_input_strfold(mpc_input_t *i, int n, mpc_val_t **xs) {
   return xs[0];
 }
 
+/*
+** Nul bytes of string input are matched, but they end the strings
+** parsers produce. A leaf whose contents are exactly what it consumed
+** without the nul bytes, followed by skipped input such as whitespace,
+** gets the nul bytes back.
+*/
+static void mpc_input_restore_nuls(mpc_input_t *i, long start, mpc_ast_t *a) {
+
+  const char *s = i->string + start;
+  long n = i->state.pos - start;
+  long j, k = 0, nuls = 0;
+
+  if (a->children_num > 0 || n <= 0 || memchr(s, '\0', n) == NULL) { return; }
+
+  for (j = 0; j < n && k < a->contents_len; j++) {
+    if (s[j] == a->contents[k]) { k++; }
+    else if (s[j] == '\0') { nuls++; }
+    else { return; }
+  }
+  if (k < a->contents_len) { return; }
+  while (j < n && s[j] == '\0') { j++; nuls++; }
+
+  if (nuls > 0) { mpc_ast_contents(a, s, j); }
+}
+
 static mpc_val_t *mpcf_input_state_ast(mpc_input_t *i, int n, mpc_val_t **xs) {
   mpc_state_t *s = ((mpc_state_t**)xs)[0];
   mpc_ast_t *a = ((mpc_ast_t**)xs)[1];
   a = mpc_ast_state(a, *s);
+  if (a != NULL && i->type == MPC_INPUT_STRING) { mpc_input_restore_nuls(i, s->pos, a); }
   mpc_free(i, s);
   (void) n;
   return a;
@@ -1032,9 +1079,13 @@ static int mpc_parse_run(mpc_input_t *i, mpc_parser_t *p, mpc_result_t *r, mpc_e
     case MPC_TYPE_RANGE:   MPC_PRIMITIVE(mpc_input_range(i, p->data.range.x, p->data.range.y, (char**)&r->output));
     case MPC_TYPE_ONEOF:   MPC_PRIMITIVE(mpc_input_oneof(i, p->data.string.x, (char**)&r->output));
     case MPC_TYPE_NONEOF:  MPC_PRIMITIVE(mpc_input_noneof(i, p->data.string.x, (char**)&r->output));
-    case MPC_TYPE_SATISFY: MPC_PRIMITIVE(mpc_input_satisfy(i, p->data.satisfy.f, (char**)&r->output));
+    case MPC_TYPE_SATISFY: MPC_PRIMITIVE(p->data.satisfy.g
+      ? mpc_input_satisfy_to(i, p->data.satisfy.g, p->data.satisfy.x, (char**)&r->output)
+      : mpc_input_satisfy(i, p->data.satisfy.f, (char**)&r->output));
     case MPC_TYPE_STRING:  MPC_PRIMITIVE(mpc_input_string(i, p->data.string.x, (char**)&r->output));
-    case MPC_TYPE_ANCHOR:  MPC_PRIMITIVE(mpc_input_anchor(i, p->data.anchor.f, (char**)&r->output));
+    case MPC_TYPE_ANCHOR:  MPC_PRIMITIVE(p->data.anchor.g
+      ? mpc_input_anchor_to(i, p->data.anchor.g, p->data.anchor.x, (char**)&r->output)
+      : mpc_input_anchor(i, p->data.anchor.f, (char**)&r->output));
 
     /* Other parsers */
 
@@ -1595,6 +1646,17 @@ mpc_parser_t *mpc_anchor(int(*f)(char,char)) {
   mpc_parser_t *p = mpc_undefined();
   p->type = MPC_TYPE_ANCHOR;
   p->data.anchor.f = f;
+  p->data.anchor.g = NULL;
+  p->data.anchor.x = NULL;
+  return mpc_expect(p, "anchor");
+}
+
+mpc_parser_t *mpc_anchor_to(int(*f)(char,char,void*), void *x) {
+  mpc_parser_t *p = mpc_undefined();
+  p->type = MPC_TYPE_ANCHOR;
+  p->data.anchor.f = NULL;
+  p->data.anchor.g = f;
+  p->data.anchor.x = x;
   return mpc_expect(p, "anchor");
 }
 
@@ -1697,9 +1759,20 @@ mpc_parser_t *mpc_satisfy(int(*f)(char)) {
   mpc_parser_t *p = mpc_undefined();
   p->type = MPC_TYPE_SATISFY;
   p->data.satisfy.f = f;
+  p->data.satisfy.g = NULL;
+  p->data.satisfy.x = NULL;
   return mpc_expectf(p, "character satisfying function %p", f);
 }
 
+mpc_parser_t *mpc_satisfy_to(int(*f)(char,void*), void *x) {
+  mpc_parser_t *p = mpc_undefined();
+  p->type = MPC_TYPE_SATISFY;
+  p->data.satisfy.f = NULL;
+  p->data.satisfy.g = f;
+  p->data.satisfy.x = x;
+  return mpc_expectf(p, "character satisfying function %p", (void*)f);
+}
+
 mpc_parser_t *mpc_string(const char *s) {
   mpc_parser_t *p = mpc_undefined();
   p->type = MPC_TYPE_STRING;
@@ -2706,7 +2779,8 @@ mpc_ast_t *mpc_ast_new(const char *tag, const char *contents) {
   a->tag = malloc(strlen(tag) + 1);
   strcpy(a->tag, tag);
 
-  a->contents = malloc(strlen(contents) + 1);
+  a->contents_len = strlen(contents);
+  a->contents = malloc(a->contents_len + 1);
   strcpy(a->contents, contents);
 
   a->state = mpc_state_new();
@@ -2717,6 +2791,16 @@ mpc_ast_t *mpc_ast_new(const char *tag, const char *contents) {
 
 }
 
+mpc_ast_t *mpc_ast_contents(mpc_ast_t *a, const char *contents, long length) {
+  if (a == NULL) { return a; }
+  free(a->contents);
+  a->contents = malloc(length + 1);
+  memcpy(a->contents, contents, length);
+  a->contents[length] = '\0';
+  a->contents_len = length;
+  return a;
+}
+
 mpc_ast_t *mpc_ast_build(int n, const char *tag, ...) {
 
   mpc_ast_t *a = mpc_ast_new(tag, "");
@@ -2753,7 +2837,8 @@ int mpc_ast_eq(mpc_ast_t *a, mpc_ast_t *b) {
   int i;
 
   if (strcmp(a->tag, b->tag) != 0) { return 0; }
-  if (strcmp(a->contents, b->contents) != 0) { return 0; }
+  if (a->contents_len != b->contents_len) { return 0; }
+  if (memcmp(a->contents, b->contents, a->contents_len) != 0) { return 0; }
   if (a->children_num != b->children_num) { return 0; }
 
   for (i = 0; i < a->children_num; i++) {
@@ -3244,6 +3329,14 @@ static mpc_parser_t *mpca_grammar_find_parser(char *x, mpca_grammar_st_t *st) {
 
     i = strtol(x, NULL, 10);
 
+    /* Parsers supplied as a NULL terminated array */
+    if (st->va == NULL) {
+      if (i >= st->parsers_num - 1) {
+        return mpc_failf("No Parser in position %i! Only supplied %i Parsers!", i, st->parsers_num - 1);
+      }
+      return st->parsers[i];
+    }
+
     while (st->parsers_num <= i) {
       st->parsers_num++;
       st->parsers = realloc(st->parsers, sizeof(mpc_parser_t*) * st->parsers_num);
@@ -3588,6 +3681,28 @@ mpc_err_t *mpca_lang(int flags, const char *language, ...) {
   return err;
 }
 
+mpc_err_t *mpca_lang_array(int flags, const char *language, int n, mpc_parser_t **parsers) {
+
+  mpca_grammar_st_t st;
+  mpc_input_t *i;
+  mpc_err_t *err;
+
+  st.va = NULL;
+  st.parsers_num = n + 1;
+  st.parsers = malloc(sizeof(mpc_parser_t*) * (n + 1));
+  st.flags = flags;
+
+  memcpy(st.parsers, parsers, sizeof(mpc_parser_t*) * n);
+  st.parsers[n] = NULL;
+
+  i = mpc_input_new_string("<mpca_lang>", language);
+  err = mpca_lang_st(i, &st);
+  mpc_input_delete(i);
+
+  free(st.parsers);
+  return err;
+}
+
 mpc_err_t *mpca_lang_contents(int flags, const char *filename, ...) {
 
   mpca_grammar_st_t st;
@@ -42,6 +42,7 @@ typedef struct {
 } mpc_err_t;
 
 void mpc_err_delete(mpc_err_t *e);
+mpc_err_t *mpc_err_failure(const char *filename, const char *failure);
 char *mpc_err_string(mpc_err_t *e);
 void mpc_err_print(mpc_err_t *e);
 void mpc_err_print_to(mpc_err_t *e, FILE *f);
@@ -99,6 +100,7 @@ mpc_parser_t *mpc_range(char s, char e);
 mpc_parser_t *mpc_oneof(const char *s);
 mpc_parser_t *mpc_noneof(const char *s);
 mpc_parser_t *mpc_satisfy(int(*f)(char));
+mpc_parser_t *mpc_satisfy_to(int(*f)(char,void*), void *x);
 mpc_parser_t *mpc_string(const char *s);
 
 /*
@@ -111,6 +113,7 @@ mpc_parser_t *mpc_failf(const char *fmt, ...);
 mpc_parser_t *mpc_lift(mpc_ctor_t f);
 mpc_parser_t *mpc_lift_val(mpc_val_t *x);
 mpc_parser_t *mpc_anchor(int(*f)(char,char));
+mpc_parser_t *mpc_anchor_to(int(*f)(char,char,void*), void *x);
 mpc_parser_t *mpc_state(void);
 
 /*
@@ -263,9 +266,11 @@ typedef struct mpc_ast_t {
   mpc_state_t state;
   int children_num;
   struct mpc_ast_t** children;
+  long contents_len;
 } mpc_ast_t;
 
 mpc_ast_t *mpc_ast_new(const char *tag, const char *contents);
+mpc_ast_t *mpc_ast_contents(mpc_ast_t *a, const char *contents, long length);
 mpc_ast_t *mpc_ast_build(int n, const char *tag, ...);
 mpc_ast_t *mpc_ast_add_root(mpc_ast_t *a);
 mpc_ast_t *mpc_ast_add_child(mpc_ast_t *r, mpc_ast_t *a);
@@ -339,6 +344,7 @@ mpc_err_t *mpca_lang(int flags, const char *language, ...);
 mpc_err_t *mpca_lang_file(int flags, FILE *f, ...);
 mpc_err_t *mpca_lang_pipe(int flags, FILE *f, ...);
 mpc_err_t *mpca_lang_contents(int flags, const char *filename, ...);
+mpc_err_t *mpca_lang_array(int flags, const char *language, int n, mpc_parser_t **parsers);
 
 /*
 ** Misc
@@ -9,23 +9,30 @@ extern
 {
 	/*
 	** Errors
-	** TODO mpc_err_print_to
 	*/
 	/// delete a `*mut mpc_err_t`, should be used instead of free()
 	pub fn mpc_err_delete(e: *mut mpc_err_t);
 	/// get an error string of error `e`
 	pub fn mpc_err_string(e: *const mpc_err_t) -> *const c_char;
+	/// create an error with the message `failure`, the strings are copied
+	pub fn mpc_err_failure(filename: *const c_char, failure: *const c_char) -> *mut mpc_err_t;
 	/// print a error to `stdout`
 	pub fn mpc_err_print(e: *const mpc_err_t);
+	/// print a error to the stream `f`
+	pub fn mpc_err_print_to(e: *const mpc_err_t, f: *mut FILE);
 
 	/*
 	** Parsing
-	** TODO mpc_parse_file & mpc_parse_pipe
 	*/
 	/// run a parser on some string
 	pub fn mpc_parse(filename: *const c_char, string: *const c_char, p: *mut mpc_parser_t, r: *const mpc_result_t) -> i32;
-	/// run a parser on `length` characters of some string
-	pub fn mpc_nparse(filename: *const c_char, string: *const c_char, length: u32, p: *mut mpc_parser_t, r: *const mpc_result_t) -> i32;
+	/// run a parser on `length` bytes of some string, which may contain nul bytes
+	pub fn mpc_nparse(filename: *const c_char, string: *const c_char, length: usize, p: *mut mpc_parser_t, r: *const mpc_result_t) -> i32;
+	/// run a parser on the contents of the seekable stream `file`
+	pub fn mpc_parse_file(filename: *const c_char, file: *mut FILE, p: *mut mpc_parser_t, r: *const mpc_result_t) -> i32;
+	/// run a parser on the contents of the stream `pipe`, which is buffered
+	/// only as far as the parser may backtrack
+	pub fn mpc_parse_pipe(filename: *const c_char, pipe: *mut FILE, p: *mut mpc_parser_t, r: *const mpc_result_t) -> i32;
 	/// parse contents of a file `filename`
 	pub fn mpc_parse_contents(filename: *const c_char, p: *mut mpc_parser_t, r: *const mpc_result_t);
 
@@ -40,6 +47,8 @@ extern
 	pub fn mpc_define(p: *mut mpc_parser_t, a: *mut mpc_parser_t) -> *mut mpc_parser_t;
 	/// undefine a parser. use this before deleting a parser
 	pub fn mpc_undefine(p: *mut mpc_parser_t) -> *mut mpc_parser_t;
+	/// delete a parser. retained parsers should be undefined first
+	pub fn mpc_delete(p: *mut mpc_parser_t);
 	/// automatically undefine and delete parsers
 	pub fn mpc_cleanup(n: i32, ...);
 
@@ -57,7 +66,9 @@ extern
 	/// matches none of characters `s`
 	pub fn mpc_noneof(s: *const c_char) -> *mut mpc_parser_t;
 	/// matches a character that satisfies function `f`
-	pub fn mpc_satisfy(f: extern fn(c: c_char) -> i32) -> *mut mpc_parser_t;
+	pub fn mpc_satisfy(f: mpc_satisfy_t) -> *mut mpc_parser_t;
+	/// matches a character that satisfies function `f`, which is passed `x` as well
+	pub fn mpc_satisfy_to(f: mpc_satisfy_to_t, x: *mut c_void) -> *mut mpc_parser_t;
 	/// matches a string `s`
 	pub fn mpc_string(s: *const c_char) -> *mut mpc_parser_t;
 
@@ -84,7 +95,9 @@ extern
 	///
 	/// At the start of the input the first argument is set to '\0'.
 	/// At the end of the input the second argument is set to '\0'.
-	pub fn mpc_anchor(f: extern fn(c1: c_char, c2: c_char) -> i32) -> *mut mpc_parser_t;
+	pub fn mpc_anchor(f: mpc_anchor_t) -> *mut mpc_parser_t;
+	/// same as `mpc_anchor`, function `f` is passed `x` as well
+	pub fn mpc_anchor_to(f: mpc_anchor_to_t, x: *mut c_void) -> *mut mpc_parser_t;
 	/// consumes no input, always successful, returns copy of the parser state as `*mut mpc_parser_t`.
 	/// the state is newly allocated and needs to be freed
 	pub fn mpc_state() -> *mut mpc_parser_t;
@@ -99,7 +112,7 @@ extern
 	/// returns a parser that applies function `f` to the result of parser `a`
 	pub fn mpc_apply(a: *mut mpc_parser_t, f: mpc_apply_t) -> *mut mpc_parser_t;
 	/// returns a parser that applies function `f` to the result of parser `a`, taking extra input `x`
-	pub fn mpc_apply_to(a: *mut mpc_parser_t, f: mpc_apply_to_t, x: *const c_void) -> *mut mpc_parser_t;
+	pub fn mpc_apply_to(a: *mut mpc_parser_t, f: mpc_apply_to_t, x: *mut c_void) -> *mut mpc_parser_t;
 
 	/// returns a parser that, if `a` succeeds, fails and consumes no input, if `a` fails, it succeeds and consumes no input.
 	/// destructor `da` is to destroy the result of `a` on success
@@ -120,7 +133,7 @@ extern
 	pub fn mpc_many1(f: mpc_fold_t, a: *mut mpc_parser_t) -> *mut mpc_parser_t;
 	/// runs parser `a` exactly `n` times, on failure, partial results are destroyed with `da`, on success
 	/// results are combined using fold function `f`
-	pub fn mpc_count(n: i32, f: mpc_fold_t, a: *mut mpc_parser_t, lf: mpc_ctor_t) -> *mut mpc_parser_t;
+	pub fn mpc_count(n: i32, f: mpc_fold_t, a: *mut mpc_parser_t, da: mpc_dtor_t) -> *mut mpc_parser_t;
 
 	/// attempts to run `n` parsers in sequence, returning the first one that succeeds
 	pub fn mpc_or(n: i32, ...) -> *mut mpc_parser_t;
@@ -230,7 +243,7 @@ extern
 	pub fn mpc_tok_parens(a: *mut mpc_parser_t, ad: mpc_dtor_t) -> *mut mpc_parser_t;
 	pub fn mpc_tok_braces(a: *mut mpc_parser_t, ad: mpc_dtor_t) -> *mut mpc_parser_t;
 	pub fn mpc_tok_brackets(a: *mut mpc_parser_t, ad: mpc_dtor_t) -> *mut mpc_parser_t;
-	pub fn mpc_tok_squares(a: mpc_parser_t, ad: mpc_dtor_t) -> *mut mpc_parser_t;
+	pub fn mpc_tok_squares(a: *mut mpc_parser_t, ad: mpc_dtor_t) -> *mut mpc_parser_t;
 
 	/*
 	** Common Function Parameters
@@ -278,11 +291,12 @@ extern
 
 	/*
 	** AST
-	** TODO mpc_ast_print_to,
-	**      mpca_lang_file, mpca_lang_pipe,
+	** TODO mpca_lang_file, mpca_lang_pipe
 	*/
 	pub fn mpc_ast_new(tag: *const c_char, contents: *const c_char) -> *mut mpc_ast_t;
-	pub fn mpc_ast_build(n: i32, tag: *const char, ...) -> *mut mpc_ast_t;
+	/// replace the contents of `a` with `length` bytes of `contents`, which may contain nul bytes
+	pub fn mpc_ast_contents(a: *mut mpc_ast_t, contents: *const c_char, length: i64) -> *mut mpc_ast_t;
+	pub fn mpc_ast_build(n: i32, tag: *const c_char, ...) -> *mut mpc_ast_t;
 	pub fn mpc_ast_add_root(a: *mut mpc_ast_t) -> *mut mpc_ast_t;
 	pub fn mpc_ast_add_child(r: *mut mpc_ast_t, a: *mut mpc_ast_t) -> *mut mpc_ast_t;
 	pub fn mpc_ast_add_tag(a: *mut mpc_ast_t, t: *const c_char) -> *mut mpc_ast_t;
@@ -292,11 +306,14 @@ extern
 
 	pub fn mpc_ast_delete(a: *mut mpc_ast_t);
 	pub fn mpc_ast_print(a: *mut mpc_ast_t);
+	pub fn mpc_ast_print_to(a: *mut mpc_ast_t, fp: *mut FILE);
+	/// compare tags, contents and children of two trees, but not their states
+	pub fn mpc_ast_eq(a: *mut mpc_ast_t, b: *mut mpc_ast_t) -> i32;
 
-	pub fn mpc_ast_get_index(ast: *mut mpc_ast_t, tag: *const c_char);
-	pub fn mpc_ast_get_index_lb(ast: *mut mpc_ast_t, tag: *const c_char, lb: i32);
-	pub fn mpc_ast_get_child(ast: *mut mpc_ast_t, tag: *const c_char);
-	pub fn mpc_ast_get_child_lb(ast: *mut mpc_ast_t, tag: *const c_char, lb: i32);
+	pub fn mpc_ast_get_index(ast: *mut mpc_ast_t, tag: *const c_char) -> i32;
+	pub fn mpc_ast_get_index_lb(ast: *mut mpc_ast_t, tag: *const c_char, lb: i32) -> i32;
+	pub fn mpc_ast_get_child(ast: *mut mpc_ast_t, tag: *const c_char) -> *mut mpc_ast_t;
+	pub fn mpc_ast_get_child_lb(ast: *mut mpc_ast_t, tag: *const c_char, lb: i32) -> *mut mpc_ast_t;
 
 	pub fn mpc_ast_traverse_start(ast: *mut mpc_ast_t, order: mpc_ast_trav_order_t) -> *mut mpc_ast_trav_t;
 	pub fn mpc_ast_traverse_next(trav: *mut *mut mpc_ast_trav_t) -> *mut mpc_ast_t;
@@ -323,8 +340,14 @@ extern
 	pub fn mpca_count(n: i32, a: *mut mpc_parser_t) -> *mut mpc_parser_t;
 
 	pub fn mpca_grammar(flags: mpca_lang_type, grammar: *const c_char, ...) -> *mut mpc_parser_t;
-	pub fn mpca_lang(flags: mpca_lang_type, grammar: *const c_char, ...) -> *mut mpc_parser_t;
-	pub fn mpca_lang_contents(flags: mpca_lang_type, filename: *const c_char, ...) -> *mut mpc_parser_t;
+	/// define the NULL terminated list of parsers using grammar `grammar`.
+	/// returns a nullptr on success, otherwise an error that must be deleted
+	pub fn mpca_lang(flags: mpca_lang_type, grammar: *const c_char, ...) -> *mut mpc_err_t;
+	/// same as `mpca_lang`, but the grammar is read from file `filename`
+	pub fn mpca_lang_contents(flags: mpca_lang_type, filename: *const c_char, ...) -> *mut mpc_err_t;
+	/// same as `mpca_lang`, but takes the `n` parsers as an array instead of a NULL terminated
+	/// variadic list, so that the set of rules may be decided at runtime
+	pub fn mpca_lang_array(flags: mpca_lang_type, grammar: *const c_char, n: i32, parsers: *mut *mut mpc_parser_t) -> *mut mpc_err_t;
 
 	/*
 	** Misc
@@ -337,17 +360,27 @@ extern
 		p: *mut mpc_parser_t,
 		s: *const c_char,
 		d: *const c_void,
-		tester: extern fn(a1: *const c_void, a2: *const c_void) -> i32,
+		tester: unsafe extern "C" fn(a1: *const c_void, a2: *const c_void) -> i32,
 		destructor: mpc_dtor_t,
-		printer: extern fn(p: *const c_void)
+		printer: unsafe extern "C" fn(p: *const c_void)
 	) -> i32;
 
 	pub fn mpc_test_fail(
 		p: *mut mpc_parser_t,
 		s: *const c_char,
 		d: *const c_void,
-		tester: extern fn(a1: *const c_void, a2: *const c_void) -> i32,
+		tester: unsafe extern "C" fn(a1: *const c_void, a2: *const c_void) -> i32,
 		destructor: mpc_dtor_t,
-		printer: extern fn(p: *const c_void)
+		printer: unsafe extern "C" fn(p: *const c_void)
 	) -> i32;
+
+	/*
+	** C standard library
+	*/
+	/// free memory allocated by mpc, such as the string returned by `mpc_err_string`
+	pub fn free(p: *mut c_void);
+	/// open the file `path` with `mode`, returns a nullptr and sets errno on failure
+	pub fn fopen(path: *const c_char, mode: *const c_char) -> *mut FILE;
+	/// close the stream `f`
+	pub fn fclose(f: *mut FILE) -> i32;
 }
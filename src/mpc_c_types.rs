@@ -3,6 +3,7 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
 
+use mpc_c::mpc_err_failure;
 use std::os::raw::{c_char, c_void};
 
 /*
@@ -45,26 +46,19 @@ pub struct mpc_err_t
 	pub failure: *const c_char,
 	/// Array of expected items
 	pub expected: *const *const c_char,
+	/// Character received at the error's position
+	pub recieved: c_char,
 }
 
 impl mpc_err_t
 {
-	/// Create a new mpc_err_t
-	pub fn new(failure: &str) -> mpc_err_t
+	/// Create a new error allocated by mpc, which copies the strings.
+	/// It must be deleted with `mpc_err_delete` or taken by `ParseError::from_raw`
+	pub fn new(failure: &str) -> *mut mpc_err_t
 	{
-		mpc_err_t
-		{
-			state: mpc_state_t
-			{
-				pos: 0,
-				row: 0,
-				col: 0,
-			},
-			expected_num: 0,
-			filename: c_str!("<input>"),
-			failure: c_str!(failure),
-			expected: 0 as *const *const c_char
-		}
+		let filename = c_str!("<input>");
+		let failure = c_str!(failure);
+		unsafe { mpc_err_failure(filename.as_ptr(), failure.as_ptr()) }
 	}
 }
 
@@ -80,15 +74,23 @@ pub type mpc_val_t = c_void;
 #[repr(C)]
 pub struct mpc_parser_t;
 /// Destructor function type
-pub type mpc_dtor_t = extern fn(val: *const mpc_val_t);
+pub type mpc_dtor_t = unsafe extern "C" fn(val: *mut mpc_val_t);
 /// Constructor function type
-pub type mpc_ctor_t = extern fn() -> *const mpc_val_t;
+pub type mpc_ctor_t = unsafe extern "C" fn() -> *mut mpc_val_t;
 /// Apply function type
-pub type mpc_apply_t = extern fn() -> *const mpc_val_t;
+pub type mpc_apply_t = unsafe extern "C" fn(val: *mut mpc_val_t) -> *mut mpc_val_t;
 /// 'Apply to' function type
-pub type mpc_apply_to_t = extern fn(val: *const mpc_val_t, *const c_void) -> mpc_val_t;
+pub type mpc_apply_to_t = unsafe extern "C" fn(val: *mut mpc_val_t, x: *mut c_void) -> *mut mpc_val_t;
 /// Fold function type
-pub type mpc_fold_t = extern fn(i: i32, v: *const *const mpc_val_t) -> mpc_val_t;
+pub type mpc_fold_t = unsafe extern "C" fn(n: i32, xs: *mut *mut mpc_val_t) -> *mut mpc_val_t;
+/// Satisfy function type
+pub type mpc_satisfy_t = unsafe extern "C" fn(c: c_char) -> i32;
+/// 'Satisfy to' function type, taking extra input like `mpc_apply_to_t`
+pub type mpc_satisfy_to_t = unsafe extern "C" fn(c: c_char, x: *mut c_void) -> i32;
+/// Anchor function type
+pub type mpc_anchor_t = unsafe extern "C" fn(last: c_char, next: c_char) -> i32;
+/// 'Anchor to' function type, taking extra input like `mpc_apply_to_t`
+pub type mpc_anchor_to_t = unsafe extern "C" fn(last: c_char, next: c_char, x: *mut c_void) -> i32;
 
 /*
 ** AST
@@ -108,15 +110,18 @@ pub struct mpc_ast_t
 	pub children_num: i32,
 	/// Child nodes
 	pub children: *mut *mut mpc_ast_t,
+	/// Length of `contents`, which may contain nul bytes
+	pub contents_len: i64,
 }
 
 /// Traversal order
-#[repr(u8)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum mpc_ast_trav_order_t
 {
-	/// From beginning to end
+	/// Parents before their children
 	mpc_ast_trav_order_pre,
-	/// From end to beginning
+	/// Children before their parents
 	mpc_ast_trav_order_post,
 }
 
@@ -125,9 +130,9 @@ pub enum mpc_ast_trav_order_t
 pub struct mpc_ast_trav_t
 {
 	/// Current node
-	pub curr_node: *const mpc_ast_t,
+	pub curr_node: *mut mpc_ast_t,
 	/// Parent traversal
-	pub parent: *const mpc_ast_trav_t,
+	pub parent: *mut mpc_ast_trav_t,
 	/// Index of current child
 	pub curr_child: i32,
 	/// mpc traversal order
@@ -159,3 +164,10 @@ pub union mpc_result_t
 	/// pointer to ast
 	pub output: *mut mpc_ast_t,
 }
+
+/*
+** C Standard Library Types
+*/
+/// C's `FILE` stream, only ever used as a pointer
+#[repr(C)]
+pub struct FILE;
@@ -0,0 +1,82 @@
+//! Contains an owned handle to a `mpc_parser_t`
+#![allow(dead_code)]
+
+use mpc_c::*;
+use mpc_c_types::*;
+use std::ffi::CString;
+use std::mem;
+
+/// An owned mpc parser. The underlying `mpc_parser_t` is undefined
+/// and deleted when the `Parser` is dropped.
+///
+/// Parsers that refer to each other (such as the rules of a grammar)
+/// must all be undefined before any of them is deleted, use `cleanup`
+/// to drop such a group.
+pub struct Parser
+{
+	raw: parser_ptr,
+}
+
+impl Parser
+{
+	/// Create a new retained and undefined parser called `name`
+	pub fn new(name: &str) -> Parser
+	{
+		let name = CString::new(name).unwrap();
+		Parser { raw: unsafe { mpc_new(name.as_ptr()) } }
+	}
+
+	/// Take ownership of a raw parser, e.g. one returned by `mpc_alpha()`
+	///
+	/// # Safety
+	///
+	/// `raw` must be a parser allocated by mpc, it is deleted with
+	/// `mpc_delete` on drop. Nothing else may free it afterwards
+	pub unsafe fn from_raw(raw: parser_ptr) -> Parser
+	{
+		Parser { raw: raw }
+	}
+
+	/// Pointer to the underlying parser
+	pub fn as_ptr(&self) -> parser_ptr
+	{
+		self.raw
+	}
+
+	/// Give up ownership of the underlying parser without freeing it
+	pub fn into_raw(self) -> parser_ptr
+	{
+		let raw = self.raw;
+		mem::forget(self);
+		raw
+	}
+
+	/// Undefine the parser, freeing everything it owns except for
+	/// retained sub-parsers. The parser itself stays allocated
+	pub fn undefine(&self)
+	{
+		unsafe { mpc_undefine(self.raw); }
+	}
+}
+
+impl Drop for Parser
+{
+	fn drop(&mut self)
+	{
+		unsafe
+		{
+			mpc_undefine(self.raw);
+			mpc_delete(self.raw);
+		}
+	}
+}
+
+/// Undefine all `parsers` and only then delete them, the Rust
+/// equivalent of `mpc_cleanup`
+pub fn cleanup(parsers: Vec<Parser>)
+{
+	for p in &parsers
+	{
+		p.undefine();
+	}
+}
@@ -0,0 +1,85 @@
+//! Contains a renderer for rustc-style annotated error reports
+#![allow(dead_code)]
+
+use colors::*;
+use error::*;
+use std::fmt;
+
+/// An annotated report of a `ParseError`, showing the offending
+/// line of the input with a caret under the error's column.
+/// Printed using `Display`
+///
+/// ```text
+/// error: expected ';' at '!'
+///  --> test.txt:1:4
+///   |
+/// 1 | abc!
+///   |    ^ expected ';'
+/// ```
+pub struct Report<'a>
+{
+	error: &'a ParseError,
+	input: &'a str,
+	colors: bool,
+}
+
+impl<'a> Report<'a>
+{
+	/// Create a colored report of `error`, which occurred while parsing `input`
+	pub fn new(error: &'a ParseError, input: &'a str) -> Report<'a>
+	{
+		Report
+		{
+			error: error,
+			input: input,
+			colors: true,
+		}
+	}
+
+	/// Enable or disable ANSI colors, plain reports are
+	/// better suited for logs and CI
+	pub fn colors(mut self, colors: bool) -> Report<'a>
+	{
+		self.colors = colors;
+		self
+	}
+
+	/// The line of input the error is located on
+	fn line(&self) -> &'a str
+	{
+		let line = self.input.split('\n').nth(self.error.row).unwrap_or("");
+		line.trim_end_matches('\r')
+	}
+}
+
+impl<'a> fmt::Display for Report<'a>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		let e = self.error;
+
+		if let Some(ref failure) = e.failure
+		{
+			writeln!(f, "{}error{}: {}", paint(self.colors, RED), paint(self.colors, RESET), failure)?;
+			return writeln!(f, " {}-->{} {}", paint(self.colors, BLUE), paint(self.colors, RESET), e.filename);
+		}
+
+		let expected = expected_list(&e.expected);
+		let row = (e.row + 1).to_string();
+		let gutter: String = row.chars().map(|_| ' ').collect();
+		let line = self.line();
+
+		// keep tabs so that the caret lines up with the line above
+		let prefix = line.get(..e.column).unwrap_or(line);
+		let indent: String = prefix.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+
+		writeln!(f, "{}error{}: expected {} at {}",
+			paint(self.colors, RED), paint(self.colors, RESET), expected, char_unescape(e.received))?;
+		writeln!(f, "{}{}-->{} {}:{}:{}",
+			gutter, paint(self.colors, BLUE), paint(self.colors, RESET), e.filename, row, e.column + 1)?;
+		writeln!(f, "{} {}|{}", gutter, paint(self.colors, BLUE), paint(self.colors, RESET))?;
+		writeln!(f, "{}{} |{} {}", paint(self.colors, BLUE), row, paint(self.colors, RESET), line)?;
+		writeln!(f, "{} {}|{} {}{}^ expected {}{}",
+			gutter, paint(self.colors, BLUE), paint(self.colors, RESET), indent, paint(self.colors, RED), expected, paint(self.colors, RESET))
+	}
+}
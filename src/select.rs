@@ -0,0 +1,370 @@
+//! Contains a CSS-like selector language for querying an `Ast`
+#![allow(dead_code)]
+
+use ast::{Ast, Child};
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// An error in the syntax of a selector
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SelectorError
+{
+	/// Byte offset in the selector where the error occurred
+	pub position: usize,
+	/// What went wrong
+	pub message: String,
+}
+
+impl fmt::Display for SelectorError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "invalid selector at {}: {}", self.position, self.message)
+	}
+}
+
+impl Error for SelectorError {}
+
+/// A compiled selector, matching descendants of the node it's run on.
+/// Selectors are made of steps separated by `>` for children or by
+/// whitespace for any descendants. A step is a tag, matched like
+/// `Ast::has_tag`, or `*` for any tag, followed by filters:
+///
+/// * `[contents="foo"]` - contents are equal to `foo`
+/// * `:nth(n)` - the `n`-th sibling (counting from zero) matching the step
+/// * `:first` and `:last` - the first and the last sibling matching the step
+///
+/// ```text
+/// sentence:nth(1) > word
+/// ident[contents="foo"]
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Selector
+{
+	steps: Vec<Step>,
+}
+
+/// Relation of a step to the previous one
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Combinator
+{
+	Child,
+	Descendant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Position
+{
+	Nth(usize),
+	Last,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Step
+{
+	/// how this step relates to the previous one, ignored for the first
+	combinator: Combinator,
+	/// `None` for `*`
+	tag: Option<String>,
+	contents: Option<String>,
+	position: Option<Position>,
+}
+
+impl Step
+{
+	/// Check `ast` ignoring the position filter
+	fn matches_node(&self, ast: &Ast) -> bool
+	{
+		self.tag.as_ref().is_none_or(|tag| ast.has_tag(tag))
+			&& self.contents.as_ref().is_none_or(|contents| ast.contents == *contents)
+	}
+
+	fn matches(&self, child: &Child) -> bool
+	{
+		if !self.matches_node(child.ast)
+		{
+			return false;
+		}
+
+		let siblings = &child.parent.children;
+		match self.position
+		{
+			None => true,
+			Some(Position::Nth(n)) =>
+				siblings[..child.index].iter().filter(|x| self.matches_node(x)).count() == n,
+			Some(Position::Last) =>
+				!siblings[child.index + 1..].iter().any(|x| self.matches_node(x)),
+		}
+	}
+}
+
+impl Selector
+{
+	/// Compile `selector`
+	pub fn new(selector: &str) -> Result<Selector, SelectorError>
+	{
+		Parser { source: selector, chars: selector.char_indices().peekable() }.parse()
+	}
+
+	/// First matching descendant of `ast` in pre-order
+	pub fn first<'a>(&self, ast: &'a Ast) -> Option<Child<'a>>
+	{
+		self.matches(ast).next()
+	}
+
+	/// All matching descendants of `ast` in pre-order
+	pub fn all<'a>(&self, ast: &'a Ast) -> Vec<Child<'a>>
+	{
+		self.matches(ast).collect()
+	}
+
+	fn matches<'a, 's>(&'s self, ast: &'a Ast) -> Matches<'a, 's>
+	{
+		Matches
+		{
+			selector: self,
+			path: Vec::new(),
+			stack: (0..ast.children.len()).rev().map(|i| (1, ast, i)).collect(),
+		}
+	}
+
+	/// Check whether `steps[..=step]` match the path ending at `path[node]`
+	fn matches_at(&self, step: usize, path: &[Child], node: usize) -> bool
+	{
+		if !self.steps[step].matches(&path[node])
+		{
+			return false;
+		}
+		if step == 0
+		{
+			return true;
+		}
+
+		match self.steps[step].combinator
+		{
+			Combinator::Child => node > 0 && self.matches_at(step - 1, path, node - 1),
+			Combinator::Descendant => (0..node).rev().any(|n| self.matches_at(step - 1, path, n)),
+		}
+	}
+}
+
+/// Iterator over the matches of a `Selector`
+struct Matches<'a, 's>
+{
+	selector: &'s Selector,
+	/// the current node and its ancestors below the root
+	path: Vec<Child<'a>>,
+	/// nodes yet to be visited as depth, parent and index
+	stack: Vec<(usize, &'a Ast, usize)>,
+}
+
+impl<'a, 's> Iterator for Matches<'a, 's>
+{
+	type Item = Child<'a>;
+
+	fn next(&mut self) -> Option<Child<'a>>
+	{
+		while let Some((depth, parent, index)) = self.stack.pop()
+		{
+			let node = &parent.children[index];
+			self.path.truncate(depth - 1);
+			self.path.push(Child
+			{
+				parent: parent,
+				ast: node,
+				index: index,
+			});
+			self.stack.extend((0..node.children.len()).rev().map(|i| (depth + 1, node, i)));
+
+			let last = self.path.len() - 1;
+			if self.selector.matches_at(self.selector.steps.len() - 1, &self.path, last)
+			{
+				return Some(self.path[last].clone());
+			}
+		}
+		None
+	}
+}
+
+/// Parser of the selector syntax
+struct Parser<'s>
+{
+	source: &'s str,
+	chars: Peekable<CharIndices<'s>>,
+}
+
+impl<'s> Parser<'s>
+{
+	fn error<T>(&mut self, message: &str) -> Result<T, SelectorError>
+	{
+		let position = self.chars.peek().map_or(self.source.len(), |&(i, _)| i);
+		Err(SelectorError { position: position, message: message.to_owned() })
+	}
+
+	fn peek(&mut self) -> Option<char>
+	{
+		self.chars.peek().map(|&(_, c)| c)
+	}
+
+	fn skip_whitespace(&mut self) -> bool
+	{
+		let mut skipped = false;
+		while self.peek().is_some_and(char::is_whitespace)
+		{
+			self.chars.next();
+			skipped = true;
+		}
+		skipped
+	}
+
+	fn expect(&mut self, c: char) -> Result<(), SelectorError>
+	{
+		if self.peek() == Some(c)
+		{
+			self.chars.next();
+			Ok(())
+		}
+		else { self.error(&format!("expected '{}'", c)) }
+	}
+
+	/// Characters up to whitespace or one of the selector's own
+	fn word(&mut self) -> String
+	{
+		let mut word = String::new();
+		while let Some(c) = self.peek()
+		{
+			if c.is_whitespace() || "[]:()>=\"'".contains(c) { break; }
+			word.push(c);
+			self.chars.next();
+		}
+		word
+	}
+
+	fn parse(mut self) -> Result<Selector, SelectorError>
+	{
+		let mut steps = Vec::new();
+		let mut combinator = Combinator::Descendant;
+		self.skip_whitespace();
+
+		loop
+		{
+			steps.push(self.step(combinator)?);
+
+			let spaced = self.skip_whitespace();
+			combinator = match self.peek()
+			{
+				None => return Ok(Selector { steps: steps }),
+				Some('>') =>
+				{
+					self.chars.next();
+					self.skip_whitespace();
+					Combinator::Child
+				},
+				Some(_) if spaced => Combinator::Descendant,
+				Some(_) => return self.error("expected '>' or whitespace"),
+			};
+		}
+	}
+
+	fn step(&mut self, combinator: Combinator) -> Result<Step, SelectorError>
+	{
+		let tag = if self.peek() == Some('*')
+		{
+			self.chars.next();
+			None
+		}
+		else
+		{
+			let tag = self.word();
+			if tag.is_empty() { return self.error("expected a tag or '*'"); }
+			Some(tag)
+		};
+
+		let mut step = Step
+		{
+			combinator: combinator,
+			tag: tag,
+			contents: None,
+			position: None,
+		};
+
+		loop
+		{
+			match self.peek()
+			{
+				Some('[') =>
+				{
+					self.chars.next();
+					if self.word() != "contents" { return self.error("expected 'contents'"); }
+					self.expect('=')?;
+					step.contents = Some(self.string()?);
+					self.expect(']')?;
+				},
+				Some(':') =>
+				{
+					self.chars.next();
+					step.position = Some(match self.word().as_str()
+					{
+						"first" => Position::Nth(0),
+						"last" => Position::Last,
+						"nth" =>
+						{
+							self.expect('(')?;
+							let n = match self.word().parse() { Ok(n) => n, Err(_) => return self.error("expected a number") };
+							self.expect(')')?;
+							Position::Nth(n)
+						},
+						_ => return self.error("expected 'nth', 'first' or 'last'"),
+					});
+				},
+				_ => return Ok(step),
+			}
+		}
+	}
+
+	/// A string quoted with `"` or `'`, a backslash escapes the next character
+	fn string(&mut self) -> Result<String, SelectorError>
+	{
+		let quote = match self.peek()
+		{
+			Some(c) if c == '"' || c == '\'' => c,
+			_ => return self.error("expected a quoted string"),
+		};
+		self.chars.next();
+
+		let mut string = String::new();
+		loop
+		{
+			match self.chars.next()
+			{
+				Some((_, c)) if c == quote => return Ok(string),
+				Some((_, '\\')) => match self.chars.next()
+				{
+					Some((_, c)) => string.push(c),
+					None => return self.error("unterminated string"),
+				},
+				Some((_, c)) => string.push(c),
+				None => return self.error("unterminated string"),
+			}
+		}
+	}
+}
+
+impl Ast
+{
+	/// First descendant matching `selector` in pre-order, see `Selector`
+	/// for the syntax. This node itself is not considered. Fails if
+	/// `selector` is invalid, use `Selector::new` to compile it once
+	pub fn select(&self, selector: &str) -> Result<Option<Child<'_>>, SelectorError>
+	{
+		Ok(Selector::new(selector)?.first(self))
+	}
+
+	/// All descendants matching `selector` in pre-order, see `Ast::select`
+	pub fn select_all(&self, selector: &str) -> Result<Vec<Child<'_>>, SelectorError>
+	{
+		Ok(Selector::new(selector)?.all(self))
+	}
+}
@@ -0,0 +1,107 @@
+//! Contains a compact serialized form of `Ast`. The regular form,
+//! a readable object with named fields, is derived on `Ast` itself.
+//! Only available with the `serde` feature
+#![cfg(feature = "serde")]
+
+use ast::{Ast, Span};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+use std::fmt;
+use std::ptr;
+
+/// Serializes the wrapped Ast as nested arrays of the form
+/// `[tag, contents, row, column, position, start, end, [children]]`,
+/// which takes considerably less space than the regular form
+#[derive(Clone, Eq, PartialEq)]
+pub struct Compact(pub Ast);
+
+/// Borrowed `Compact` used to serialize children without cloning them
+struct CompactRef<'a>(&'a Ast);
+
+struct CompactChildren<'a>(&'a [Ast]);
+
+impl<'a> Serialize for CompactRef<'a>
+{
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	{
+		let ast = self.0;
+		let mut tuple = serializer.serialize_tuple(8)?;
+		tuple.serialize_element(&ast.tag)?;
+		tuple.serialize_element(&ast.contents)?;
+		tuple.serialize_element(&ast.row)?;
+		tuple.serialize_element(&ast.column)?;
+		tuple.serialize_element(&ast.position)?;
+		tuple.serialize_element(&ast.span.start)?;
+		tuple.serialize_element(&ast.span.end)?;
+		tuple.serialize_element(&CompactChildren(&ast.children))?;
+		tuple.end()
+	}
+}
+
+impl<'a> Serialize for CompactChildren<'a>
+{
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	{
+		serializer.collect_seq(self.0.iter().map(CompactRef))
+	}
+}
+
+impl Serialize for Compact
+{
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	{
+		CompactRef(&self.0).serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for Compact
+{
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Compact, D::Error>
+	{
+		deserializer.deserialize_tuple(8, CompactVisitor)
+	}
+}
+
+struct CompactVisitor;
+
+impl<'de> de::Visitor<'de> for CompactVisitor
+{
+	type Value = Compact;
+
+	fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "an array [tag, contents, row, column, position, start, end, [children]]")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Compact, A::Error>
+	{
+		macro_rules! element
+		{
+			($i:expr) =>
+			{
+				seq.next_element()?.ok_or_else(|| de::Error::invalid_length($i, &self))?
+			}
+		}
+
+		let tag = element!(0);
+		let contents = element!(1);
+		let row = element!(2);
+		let column = element!(3);
+		let position = element!(4);
+		let start = element!(5);
+		let end = element!(6);
+		let children: Vec<Compact> = element!(7);
+
+		Ok(Compact(Ast
+		{
+			raw_ast: ptr::null_mut(),
+			tag: tag,
+			contents: contents,
+			children: children.into_iter().map(|c| c.0).collect(),
+			row: row,
+			column: column,
+			position: position,
+			span: Span { start: start, end: end },
+		}))
+	}
+}
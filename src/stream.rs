@@ -0,0 +1,131 @@
+//! Contains C streams over files and Rust readers, through which
+//! mpc reads input without it having to be loaded into a string first
+#![allow(dead_code)]
+
+use glue::*;
+use mpc_c::*;
+use mpc_c_types::*;
+use std::ffi::CString;
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_long, c_void};
+use std::path::Path;
+use std::slice;
+
+/// An open C stream, closed when dropped
+pub struct Stream
+{
+	raw: *mut FILE,
+}
+
+impl Stream
+{
+	/// Open the file at `path` for reading
+	pub fn open(path: &Path) -> io::Result<Stream>
+	{
+		let path = path_to_cstring(path)?;
+		let raw = unsafe { fopen(path.as_ptr(), b"rb\0".as_ptr() as *const c_char) };
+		if raw.is_null()
+		{
+			return Err(io::Error::last_os_error());
+		}
+		Ok(Stream { raw: raw })
+	}
+
+	/// Pointer to the underlying stream
+	pub fn as_ptr(&self) -> *mut FILE
+	{
+		self.raw
+	}
+}
+
+impl Drop for Stream
+{
+	fn drop(&mut self)
+	{
+		unsafe { fclose(self.raw); }
+	}
+}
+
+/// Rust reader behind a `ReaderStream` along with the
+/// first error it returned
+struct ReadState<R>
+{
+	reader: R,
+	error: Option<io::Error>,
+}
+
+/// A C stream reading from a Rust reader. Supported where the C library
+/// provides custom streams, that is on glibc, macOS and the BSDs
+pub struct ReaderStream<R>
+{
+	// declared first, so that it's closed before the state is freed
+	stream: Stream,
+	state: Box<ReadState<R>>,
+}
+
+impl<R: Read> ReaderStream<R>
+{
+	/// Open a stream over `reader`
+	pub fn new(reader: R) -> io::Result<ReaderStream<R>>
+	{
+		let mut state = Box::new(ReadState { reader: reader, error: None });
+		let x = &mut *state as *mut ReadState<R> as *mut c_void;
+		let raw = unsafe { glue_reader(read_into::<R>, x) };
+		if raw.is_null()
+		{
+			return Err(io::Error::new(io::ErrorKind::Unsupported, "custom C streams are not supported on this platform"));
+		}
+		Ok(ReaderStream { stream: Stream { raw: raw }, state: state })
+	}
+
+	/// Pointer to the underlying stream
+	pub fn as_ptr(&self) -> *mut FILE
+	{
+		self.stream.as_ptr()
+	}
+
+	/// Take the error the reader returned, if any
+	pub fn take_error(&mut self) -> Option<io::Error>
+	{
+		self.state.error.take()
+	}
+}
+
+/// `glue_read_t` reading from a `ReadState<R>`, retrying interrupted reads
+/// and keeping the error for the reader's owner. It must not panic
+unsafe extern "C" fn read_into<R: Read>(x: *mut c_void, buf: *mut c_char, size: usize) -> c_long
+{
+	let state = &mut *(x as *mut ReadState<R>);
+	let buf = slice::from_raw_parts_mut(buf as *mut u8, size);
+	loop
+	{
+		match state.reader.read(buf)
+		{
+			// a safe reader may still claim more than fits
+			Ok(n) => return n.min(size) as c_long,
+			Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(e) =>
+			{
+				state.error = Some(e);
+				return -1;
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+fn path_to_cstring(path: &Path) -> io::Result<CString>
+{
+	use std::os::unix::ffi::OsStrExt;
+	CString::new(path.as_os_str().as_bytes())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
+}
+
+#[cfg(not(unix))]
+fn path_to_cstring(path: &Path) -> io::Result<CString>
+{
+	let path = path.to_str()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid unicode"))?;
+	CString::new(path)
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
+}
@@ -1,9 +1,53 @@
 #![cfg(test)]
 use glue;
 use ast::*;
+use builder::*;
 use mpc_c::*;
 use mpc_c_types::*;
+use parser::*;
+use grammar::*;
+use error::*;
+use visit::*;
+use diff;
+use select;
+use combinators as p;
+use callback;
+use typed;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::{self, Read};
+use std::os::raw::c_void;
 
+/// The grammar of the examples, prepared for the tests using it repeatedly
+fn sentence_grammar() -> Language
+{
+	parser!
+	{
+		grammar:
+		{g_string![
+			"word : /[a-zA-Z0-9]+/;                         \n"
+			"punct: '.' | '!' | ',' | ';' | '?' | '-' | ':';\n"
+			"sentence: <word>+ <punct>;                     \n"
+			"paragraph: <sentence>+;                        \n"
+		]}
+		main: paragraph
+		parsers: word punct sentence
+	}.unwrap()
+}
+
+/// Pairs of words, such as `(a b)`, parsed as a single `pair` or as a
+/// sequence of them when `main` is `pairs`. Words may contain quotes
+fn pair_grammar(main: &str) -> Language
+{
+	let grammar = Grammar::new("word : /[a-z\"]+/; pair : '(' <word> <word> ')'; pairs : <pair>+;").main(main);
+	["word", "pair", "pairs"].iter()
+		.filter(|&&rule| rule != main)
+		.fold(grammar, |grammar, rule| grammar.rule(rule))
+		.build()
+		.unwrap()
+}
 
 #[test]
 fn mpca_parse()
@@ -32,12 +76,12 @@ fn mpca_parse()
 		Ok(ast) =>
 		{
 			println!("success!");
-			unsafe { mpc_ast_print(ast); }
+			unsafe { mpc_ast_print(ast.as_ptr()); }
 		},
 		Err(r) =>
 		{
 			println!("fail!");
-			unsafe { mpc_err_print(r); }
+			print!("{}", r);
 			panic!();
 		}
 	}
@@ -69,14 +113,14 @@ fn make_ast()
 	{
 		Ok(a) =>
 		{
-			let ast: Ast = Ast::new(a);
+			let ast: Ast = a.into_ast();
 			traceln!("\nlen:" ast.children.len());
 			traceln!("tag:" ast.tag);
 		},
 		Err(r) =>
 		{
 			println!("fail!");
-			unsafe { mpc_err_print(r); }
+			print!("{}", r);
 			panic!();
 		}
 	}
@@ -108,13 +152,13 @@ fn print_ast()
 	{
 		Ok(a) =>
 		{
-			let ast: Ast = Ast::new(a);
+			let ast: Ast = a.into_ast();
 			ast.print();
 		},
 		Err(r) =>
 		{
 			println!("fail!");
-			unsafe { mpc_err_print(r); }
+			print!("{}", r);
 			panic!();
 		}
 	}
@@ -134,7 +178,7 @@ fn delayed_parse()
 		]}
 		main: paragraph
 		parsers: word punct sentence
-	};
+	}.unwrap();
 
 	let result = run_parser!
 	{
@@ -150,14 +194,14 @@ fn delayed_parse()
 	{
 		Ok(a) =>
 		{
-			let ast: Ast = Ast::new(a);
+			let ast: Ast = a.into_ast();
 			traceln!("\nlen:" ast.children.len());
 			traceln!("tag:" ast.tag);
 		},
 		Err(r) =>
 		{
 			println!("fail!");
-			unsafe { mpc_err_print(r); }
+			print!("{}", r);
 			panic!();
 		}
 	}
@@ -166,11 +210,1053 @@ fn delayed_parse()
 #[test]
 fn manual_parse()
 {
+	let alpha = unsafe { Parser::from_raw(mpc_alpha()) };
+
 	unsafe
 	{
-		if let Err(_) = glue::parse(c_str!("test text"), c_str!("a"), mpc_alpha())
+		match glue::parse(&c_str!("test text"), &c_str!("a"), alpha.as_ptr())
 		{
-			panic!()
+			// the output of mpc_alpha is the matched string
+			Ok(a) => free(a as *mut c_void),
+			Err(e) =>
+			{
+				mpc_err_delete(e);
+				panic!()
+			}
 		}
 	}
 }
+
+#[test]
+fn parser_drop()
+{
+	let word = Parser::new("word");
+	let alpha = unsafe { Parser::from_raw(mpc_alpha()) };
+
+	unsafe
+	{
+		mpc_define(word.as_ptr(), mpc_maybe(alpha.into_raw()));
+		match glue::parse(&c_str!("test text"), &c_str!("a"), word.as_ptr())
+		{
+			Ok(a) => free(a as *mut c_void),
+			Err(e) =>
+			{
+				mpc_err_delete(e);
+				panic!()
+			}
+		}
+	}
+
+	cleanup(vec![word]);
+}
+
+#[test]
+fn grammar_builder()
+{
+	let language = Grammar::new(g_string![
+			"word : /[a-zA-Z0-9]+/;                         \n"
+			"punct: '.' | '!' | ',' | ';' | '?' | '-' | ':';\n"
+			"sentence: <word>+ <punct>;                     \n"
+			"paragraph: <sentence>+;                        \n"
+		])
+		.rule("word")
+		.rule("punct")
+		.rule("sentence")
+		.main("paragraph")
+		.build()
+		.unwrap();
+
+	assert_eq!(language.rules(), &["paragraph", "word", "punct", "sentence"]);
+	match language.parse("test.txt", "Bananas are awesome.")
+	{
+		Ok(a) => assert_eq!(a.into_ast().children[0].children.len(), 4),
+		Err(r) =>
+		{
+			print!("{}", r);
+			panic!();
+		}
+	}
+}
+
+#[test]
+fn grammar_builder_errors()
+{
+	assert_eq!(Grammar::new("word: /a/;").rule("word").build().err(), Some(GrammarError::NoMain));
+	assert_eq!(
+		Grammar::new("word: /a/;").main("word").rule("word").build().err(),
+		Some(GrammarError::DuplicateRule("word".to_owned()))
+	);
+	match Grammar::new("word : /a/;\nsentence <word>+;").main("word").rule("sentence").build()
+	{
+		Err(e @ GrammarError::Syntax { .. }) =>
+		{
+			if let GrammarError::Syntax { row, column, ref expected, .. } = e
+			{
+				assert_eq!((row, column), (1, 9));
+				assert!(expected.contains(&"\":\"".to_owned()));
+			}
+			traceln!(e);
+		},
+		_ => panic!(),
+	}
+
+	// parser! reports them instead of panicking
+	let result = parser!
+	{
+		grammar: {"word : /a/;\nsentence <word>+;"}
+		input: {"a"}
+		main: sentence
+		parsers: word
+	};
+	match result
+	{
+		Err(LanguageError::Grammar(GrammarError::Syntax { row, .. })) => assert_eq!(row, 1),
+		_ => panic!("expected a grammar error"),
+	}
+}
+
+#[test]
+fn parse_error()
+{
+	let language = Grammar::new("word : /[a-z]+/ ';';")
+		.main("word")
+		.build()
+		.unwrap();
+
+	let filename = CString::new("test.txt").unwrap();
+	let input = CString::new("abc!").unwrap();
+	match unsafe { glue::parse(&filename, &input, language.main().as_ptr()) }
+	{
+		Ok(_) => panic!(),
+		Err(r) =>
+		{
+			let raw = unsafe
+			{
+				let msg = mpc_err_string(r);
+				let text = CStr::from_ptr(msg).to_string_lossy().into_owned();
+				free(msg as *mut c_void);
+				text
+			};
+			let error = unsafe { ParseError::from_raw(r) };
+
+			assert_eq!(error.filename, "test.txt");
+			assert_eq!((error.row, error.column, error.position), (0, 3, 3));
+			assert_eq!(error.received, '!');
+			assert_eq!(error.to_string(), raw);
+		}
+	}
+}
+
+#[test]
+fn error_report()
+{
+	let input = "word word;\nword\tword!;\n";
+	let language = Grammar::new("word \"word\" : /[a-z]+/; line : <word>+ ';'; text : /^/ <line>+ /$/;")
+		.rule("word")
+		.rule("line")
+		.main("text")
+		.build()
+		.unwrap();
+
+	match language.parse("test.txt", input)
+	{
+		Ok(_) => panic!(),
+		Err(e) =>
+		{
+			let report = e.report(input).colors(false).to_string();
+			assert_eq!(report, g_string![
+				"error: expected word or ';' at '!'\n"
+				" --> test.txt:2:10\n"
+				"  |\n"
+				"2 | word\tword!;\n"
+				"  |     \t    ^ expected word or ';'\n"
+			]);
+			print!("{}", e.report(input));
+		}
+	}
+}
+
+#[test]
+fn ast_spans()
+{
+	let input = "Bananas are awesome. What do you mean, potato?";
+	let result = sentence_grammar().parse("<input>", input);
+
+	match result
+	{
+		Ok(a) =>
+		{
+			let ast = a.into_ast();
+			let first = &ast.children[0];
+			let second = &ast.children[1];
+
+			assert_eq!(ast.span, Span { start: 0, end: input.len() });
+			assert_eq!(first.source_text(input), "Bananas are awesome.");
+			assert_eq!(second.source_text(input), "What do you mean,");
+			assert_eq!(second.children[4].source_text(input), ",");
+			assert_eq!(ast.children[2].children[0].span, Span { start: 39, end: 45 });
+		},
+		Err(r) =>
+		{
+			print!("{}", r);
+			panic!();
+		}
+	}
+
+	// leaves matching part of a multi-byte character span its bytes only
+	let language = Grammar::new("c : /./ ; cs : <c>+ ;").rule("c").main("cs").build().unwrap();
+	let tree = language.parse_str("é").unwrap();
+	let ast = tree.to_ast();
+	assert_eq!(ast.children[0].span, Span { start: 0, end: 1 });
+	assert_eq!(ast.children[1].span, Span { start: 1, end: 2 });
+	assert_eq!(ast.source_text("é"), "é");
+	assert_eq!(ast.span, tree.root().span());
+
+	let tree = language.parse_bytes("<input>", b"a\xffb").unwrap();
+	assert_eq!(tree.to_ast().children[1].span, tree.root().child(1).unwrap().span());
+}
+
+#[test]
+fn borrowed_tree()
+{
+	let input = "Bananas are awesome. What do you mean, potato?";
+	let result = sentence_grammar().parse("<input>", input);
+
+	match result
+	{
+		Ok(a) =>
+		{
+			let tree = a;
+			let root = tree.root();
+			let last = root.children().next_back().unwrap();
+
+			assert_eq!(root.children().len(), 3);
+			assert_eq!(last.tag(), "sentence|>");
+			assert_eq!(last.child(0).unwrap().contents(), Ok("potato"));
+			assert_eq!(last.source_text(input), "potato?");
+			assert_eq!(root.span(), root.to_ast().span);
+		},
+		Err(r) =>
+		{
+			print!("{}", r);
+			panic!();
+		}
+	}
+}
+
+#[test]
+fn owned_ast()
+{
+	let language = Grammar::new("word : /[a-z]+/; words : <word>+;")
+		.rule("word")
+		.main("words")
+		.build()
+		.unwrap();
+
+	let tree = language.parse("<input>", "abc def").unwrap();
+	let borrowed = tree.to_ast();
+	assert_eq!(borrowed.raw_ast, tree.as_ptr());
+	assert_eq!(borrowed.children[1].raw_ast as *const mpc_ast_t, tree.root().child(1).unwrap().as_ptr());
+	assert!(borrowed.clone().raw_ast.is_null());
+
+	// copies of different parses of the same input are equal
+	let again = language.parse("<input>", "abc def").unwrap();
+	assert_eq!(again.to_ast(), borrowed);
+
+	let owned = tree.into_ast();
+	assert!(owned.raw_ast.is_null());
+	assert!(owned.children.iter().all(|c| c.raw_ast.is_null()));
+	assert_eq!(owned.children[1].contents, "def");
+}
+
+#[test]
+fn child_queries()
+{
+	let language = sentence_grammar();
+
+	let ast = language.parse("test.txt", "Bananas are awesome. What do you mean, potato?")
+		.unwrap()
+		.into_ast();
+
+	let second = ast.nth_by_tag("sentence", 1).unwrap();
+	assert_eq!(second.index, 1);
+	assert_eq!(second.ast.children[0].contents, "What");
+
+	let word = second.ast.by_tag("word").unwrap();
+	assert_eq!((word.index, word.ast.tag.as_str()), (0, "word|regex"));
+	assert_eq!(second.ast.by_tag("regex").unwrap().index, 0);
+	assert_eq!(second.ast.by_contents("mean").unwrap().index, 3);
+	assert_eq!(second.ast.by_tag_prefix("pun").unwrap().index, 4);
+	assert!(second.ast.by_tag("sentence").is_none());
+
+	let words: Vec<&str> = second.ast.children_by_tag("word").map(|c| c.ast.contents.as_str()).collect();
+	assert_eq!(words, ["What", "do", "you", "mean"]);
+
+	let punct = ast.find_descendant("punct").unwrap();
+	assert_eq!((punct.index, punct.ast.contents.as_str()), (3, "."));
+	assert_eq!(punct.parent.children[0].contents, "Bananas");
+}
+
+#[test]
+fn ast_traversal()
+{
+	let language = pair_grammar("pairs");
+
+	let tree = language.parse("<input>", "(a b) (c d)").unwrap();
+	let ast = tree.to_ast();
+
+	let pre: Vec<(usize, &str)> = ast.iter_preorder().map(|(d, x)| (d, x.contents.as_str())).collect();
+	assert_eq!(pre, [
+		(0, ""),
+		(1, ""), (2, "("), (2, "a"), (2, "b"), (2, ")"),
+		(1, ""), (2, "("), (2, "c"), (2, "d"), (2, ")"),
+	]);
+
+	let post: Vec<(usize, &str)> = ast.iter_postorder().map(|(d, x)| (d, x.contents.as_str())).collect();
+	assert_eq!(post, [
+		(2, "("), (2, "a"), (2, "b"), (2, ")"), (1, ""),
+		(2, "("), (2, "c"), (2, "d"), (2, ")"), (1, ""),
+		(0, ""),
+	]);
+
+	let raw_pre: Vec<(usize, &str)> = tree.root().iter_preorder().map(|(d, x)| (d, x.contents().unwrap())).collect();
+	assert_eq!(raw_pre, pre);
+	let raw_post: Vec<(usize, &str)> = tree.root().iter_postorder().map(|(d, x)| (d, x.contents().unwrap())).collect();
+	assert_eq!(raw_post, post);
+
+	// stopping early must not leak the traversal
+	assert_eq!(tree.root().iter_preorder().nth(2).unwrap().1.contents(), Ok("("));
+}
+
+#[test]
+fn visitors()
+{
+	struct Depth
+	{
+		current: usize,
+		max: usize,
+		trace: Vec<String>,
+	}
+
+	impl Visitor for Depth
+	{
+		fn enter(&mut self, ast: &Ast)
+		{
+			self.current += 1;
+			if self.current > self.max { self.max = self.current; }
+			self.trace.push(format!("+{}", ast.contents));
+		}
+
+		fn leave(&mut self, ast: &Ast)
+		{
+			self.current -= 1;
+			self.trace.push(format!("-{}", ast.contents));
+		}
+	}
+
+	struct Upper;
+
+	impl Folder for Upper
+	{
+		fn fold(&mut self, mut ast: Ast) -> Ast
+		{
+			ast.contents = ast.contents.to_uppercase();
+			ast.children.retain(|c| !c.has_tag("char"));
+			ast
+		}
+	}
+
+	let language = pair_grammar("pairs");
+	let ast = language.parse("<input>", "(a b) (c d)").unwrap().into_ast();
+
+	let mut depth = Depth { current: 0, max: 0, trace: Vec::new() };
+	ast.walk(&mut depth);
+	assert_eq!((depth.current, depth.max), (0, 3));
+	assert_eq!(&depth.trace[..6], ["+", "+", "+(", "-(", "+a", "-a"]);
+
+	let mut words = Vec::new();
+	let mut pairs = 0;
+	{
+		let mut visitor = TagVisitor::new()
+			.visit_tag("word", |x| words.push(x.contents.clone()))
+			.leave_tag("pair", |_| pairs += 1);
+		ast.walk(&mut visitor);
+	}
+	assert_eq!(words, ["a", "b", "c", "d"]);
+	assert_eq!(pairs, 2);
+
+	let folded = ast.fold(&mut Upper);
+	let leaves: Vec<&str> = folded.iter_preorder()
+		.filter(|&(_, x)| x.children.is_empty())
+		.map(|(_, x)| x.contents.as_str())
+		.collect();
+	assert_eq!(leaves, ["A", "B", "C", "D"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip()
+{
+	use bincode;
+	use serde_json;
+	use serialize::Compact;
+
+	let language = pair_grammar("pairs");
+	let ast = language.parse("<input>", "(a b)").unwrap().into_ast();
+
+	let json = serde_json::to_string(&ast).unwrap();
+	assert!(json.starts_with(r#"{"tag":">","contents":"","children":[{"tag":"pair|>""#));
+	assert!(!json.contains("raw_ast"));
+	assert!(json.contains(r#""span":{"start":1,"end":2}"#));
+	assert!(ast == serde_json::from_str::<Ast>(&json).unwrap());
+
+	let compact = serde_json::to_string(&Compact(ast.clone())).unwrap();
+	assert!(compact.starts_with(r#"[">","",0,0,0,0,5,[["pair|>","""#));
+	assert!(compact.contains(r#"["word|regex","a",0,1,1,1,2,[]]"#));
+	assert!(compact.len() < json.len());
+	assert!(ast == serde_json::from_str::<Compact>(&compact).unwrap().0);
+
+	// formats that aren't self-describing read both forms back too
+	let bytes = bincode::serialize(&ast).unwrap();
+	assert!(ast == bincode::deserialize::<Ast>(&bytes).unwrap());
+	let bytes = bincode::serialize(&Compact(ast.clone())).unwrap();
+	assert!(ast == bincode::deserialize::<Compact>(&bytes).unwrap().0);
+}
+
+#[test]
+fn ast_exporters()
+{
+	let language = pair_grammar("pairs");
+	let ast = language.parse("<input>", "(a \"b\")").unwrap().into_ast();
+
+	assert_eq!(ast.to_sexpr(), g_string![
+		"(>\n"
+		"  (pair|>\n"
+		"    (char \"(\")\n"
+		"    (word|regex \"a\")\n"
+		"    (word|regex \"\\\"b\\\"\")\n"
+		"    (char \")\")))"
+	]);
+
+	let mut dot = String::new();
+	ast.children[0].write_dot(&mut dot).unwrap();
+	assert_eq!(dot, g_string![
+		"digraph ast {\n"
+		"  n0 [label=\"pair|>\"];\n"
+		"  n1 [shape=box, label=\"char\\n\\\"(\\\"\"];\n"
+		"  n0 -> n1;\n"
+		"  n2 [shape=box, label=\"word|regex\\n\\\"a\\\"\"];\n"
+		"  n0 -> n2;\n"
+		"  n3 [shape=box, label=\"word|regex\\n\\\"\\\\\\\"b\\\\\\\"\\\"\"];\n"
+		"  n0 -> n3;\n"
+		"  n4 [shape=box, label=\"char\\n\\\")\\\"\"];\n"
+		"  n0 -> n4;\n"
+		"}\n"
+	]);
+	assert_eq!(ast.to_dot().matches("->").count(), 5);
+}
+
+#[test]
+fn ast_printing()
+{
+	let language = pair_grammar("pair");
+	let ast = language.parse("<input>", "(a\n b)").unwrap().into_ast();
+
+	assert_eq!(ast.to_string(), g_string![
+		">\n"
+		"  char:1:1 '('\n"
+		"  word|regex:1:2 'a'\n"
+		"  word|regex:2:2 'b'\n"
+		"  char:2:3 ')'\n"
+	]);
+
+	let mut out = Vec::new();
+	ast.write_to(&mut out, PrintOptions::default().positions(false).indent(4)).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(), g_string![
+		">\n"
+		"    char '('\n"
+		"    word|regex 'a'\n"
+		"    word|regex 'b'\n"
+		"    char ')'\n"
+	]);
+
+	let mut out = Vec::new();
+	ast.children[1].write_to(&mut out, PrintOptions::default().colors(true)).unwrap();
+	assert_eq!(String::from_utf8(out).unwrap(),
+		"\x1b[31mword|regex\x1b[0m:\x1b[32m1\x1b[0m:\x1b[33m2\x1b[0m 'a'\n");
+
+	let debug = format!("{:?}", ast.children[1]);
+	assert!(debug.starts_with("Ast { tag: \"word|regex\", contents: \"a\""));
+	assert!(!debug.contains("raw_ast"));
+}
+
+#[test]
+fn build_ast()
+{
+	let language = pair_grammar("pair");
+	let tree = language.parse("<input>", "(a b)").unwrap();
+
+	let expected = ast!(">", [
+		ast!("char" => "("),
+		ast!("word|regex" => "a"),
+		ast!("word|regex" => "b"),
+		ast!("char" => ")"),
+	]);
+	assert!(tree == expected.to_raw());
+	assert!(tree.root().child(1) == expected.to_raw().root().child(1));
+	assert!(tree != ast!(">", [ast!("char" => "(")]).to_raw());
+
+	// with locations the copies are equal too
+	let located = AstBuilder::new(">")
+		.child(AstBuilder::new("char").contents("("))
+		.child(AstBuilder::new("word|regex").contents("a").at(0, 1, 1))
+		.child(AstBuilder::new("word|regex").contents("b").at(0, 3, 3))
+		.child(AstBuilder::new("char").contents(")").at(0, 4, 4))
+		.build();
+	assert_eq!(located, tree.into_ast());
+	assert_eq!(located.to_raw().into_ast(), located);
+
+	// nested children keep their order
+	let nested = ast!(">", [
+		ast!("pair|>", [ast!("char" => "("), ast!("word" => "a"), ast!("char" => ")")]),
+		ast!("list|>", [ast!("pair|>", [ast!("word" => "b")]), ast!("word" => "c")]),
+		ast!("word" => "d"),
+	]);
+	assert_eq!(nested.to_raw().into_ast(), nested);
+
+	// no node of the copy refers to the deleted tree
+	let mut deep = ast!("word" => "a");
+	for _ in 0..1000
+	{
+		deep = AstBuilder::new("list").child(deep).build();
+	}
+	let copy = deep.to_raw().into_ast();
+	let mut node = &copy;
+	while let Some(child) = node.children.first()
+	{
+		assert!(node.raw_ast.is_null());
+		node = child;
+	}
+	assert_eq!((node.contents.as_str(), node.raw_ast.is_null()), ("a", true));
+}
+
+#[test]
+fn ast_diff()
+{
+	let language = sentence_grammar();
+	let old = language.parse("<input>", "A big brown dog. Run!").unwrap().into_ast();
+	let new = language.parse("<input>", "A small dog. Run!").unwrap().into_ast();
+
+	assert!(diff(&old, &old).is_empty());
+
+	let changes = diff::diff_with(&old, &new, diff::DiffOptions::default().positions(false));
+	assert_eq!(changes.len(), 2);
+	assert_eq!(changes[0], diff::AstChange::Contents
+	{
+		path: ">/sentence[0]/word[1]".to_owned(),
+		old: "big".to_owned(),
+		new: "small".to_owned(),
+	});
+	assert_eq!(changes[1].path(), ">/sentence[0]/word[2]");
+	assert_eq!(diff::DiffReport::new(&changes).colors(false).to_string(), g_string![
+		"~ >/sentence[0]/word[1]: 'big' -> 'small'\n"
+		"- >/sentence[0]/word[2]: word|regex 'brown'\n"
+	]);
+
+	// everything after the changed words moved
+	let changes = diff(&old, &new);
+	assert!(changes.iter().any(|c| *c == diff::AstChange::Moved
+	{
+		path: ">/sentence[1]/word[0]".to_owned(),
+		old: (0, 17),
+		new: (0, 13),
+	}));
+
+	let retagged = diff(&ast!(">", [ast!("word" => "a")]), &ast!(">", [ast!("punct" => "a")]));
+	assert_eq!(retagged, vec![diff::AstChange::Retagged
+	{
+		path: ">/punct[0]".to_owned(),
+		old: "word".to_owned(),
+		new: "punct".to_owned(),
+	}]);
+
+	// deep trees are compared without recursing
+	let (mut old, mut new) = (ast!("word" => "a"), ast!("word" => "b"));
+	for _ in 0..5000
+	{
+		old = AstBuilder::new("list").child(old).build();
+		new = AstBuilder::new("list").child(new).build();
+	}
+	let changes = diff(&old, &new);
+	assert_eq!(changes.len(), 1);
+	assert!(changes[0].path().ends_with("/list[0]/word[0]"));
+}
+
+#[test]
+fn selectors()
+{
+	let language = sentence_grammar();
+	let ast = language.parse("<input>", "A big dog. Run foo run! The end.").unwrap().into_ast();
+
+	let words: Vec<_> = ast.select_all("sentence:nth(1) > word").unwrap().iter().map(|c| c.ast.contents.clone()).collect();
+	assert_eq!(words, ["Run", "foo", "run"]);
+
+	let foo = ast.select("word[contents=\"foo\"]").unwrap().unwrap();
+	assert_eq!(foo.index, 1);
+	assert!(foo.parent.has_tag("sentence"));
+
+	assert_eq!(ast.select("sentence:last word:first").unwrap().unwrap().ast.contents, "The");
+	assert_eq!(ast.select_all("* > punct").unwrap().len(), 3);
+	assert_eq!(ast.select_all("word").unwrap().len(), 8);
+	assert!(ast.select("punct > word").unwrap().is_none());
+
+	let error = select::Selector::new("sentence:second").unwrap_err();
+	assert_eq!(error.position, 15);
+	assert!(select::Selector::new("word[contents='a]").is_err());
+	assert!(select::Selector::new("").is_err());
+	assert_eq!(ast.select("word:nth(x)").err().unwrap().position, 10);
+	assert!(ast.select_all("word >").is_err());
+}
+
+#[test]
+fn combinators()
+{
+	let keyword = p::string("if").tok().then(p::ident().tag("name").tok());
+	let tree = keyword.many1().total().parse("<input>", "if x if yy").unwrap();
+	assert!(tree == ast!(">", [
+		ast!("string" => "if"),
+		ast!("name|ident" => "x"),
+		ast!("string" => "if"),
+		ast!("name|ident" => "yy"),
+	]).to_raw());
+	assert_eq!(tree.root().child(3).unwrap().position(), 8);
+
+	let digit_or_x = !p::digits().or(p::char('x'));
+	let error = digit_or_x.parse("<input>", "12").err().unwrap();
+	assert_eq!(error.position, 0);
+
+	let empty = p::alpha().many().parse("<input>", "123").unwrap();
+	assert_eq!(empty.root().tag(), ">");
+	assert_eq!(empty.root().children_num(), 0);
+
+	// rules referring to themselves, freed along with the parsers using them
+	let rules = p::Rules::new();
+	let list = rules.rule("list");
+	rules.define(list, p::digits().or(list).tok().many().between("(", ")"));
+	let nested = p::string("list:").tok().then(list).total();
+
+	let ast = nested.parse("<input>", "list: (1 (2 3) ())").unwrap().into_ast();
+	assert_eq!(ast.select_all("list").unwrap().len(), 3);
+	assert_eq!(ast.select("list > list > digits:last").unwrap().unwrap().ast.contents, "3");
+	assert!(list.parse("<input>", "(1").is_err());
+
+	// rules of other sets stay alive as long as definitions refer to them
+	let outer = p::Rules::new();
+	let numbers = outer.rule("numbers");
+	{
+		let inner = p::Rules::new();
+		let number = inner.rule("number");
+		inner.define(number, p::digits().tok());
+		outer.define(numbers, p::Combinator::from(number).many1());
+		assert_eq!(numbers.parse("<input>", "1 2").unwrap().into_ast().select_all("number").unwrap().len(), 2);
+	}
+	drop(outer);
+
+	let bytes = p::any().many().parse("<input>", "a\0b").unwrap();
+	assert_eq!(bytes.root().child(1).unwrap().contents_bytes(), b"\0");
+	assert_eq!(p::digits().parse("a\0b", "c").err().unwrap().filename, "a\\0b");
+}
+
+#[test]
+fn closure_callbacks()
+{
+	// digits folded into their sum, values are boxed Rust values
+	// the callbacks live until the end of the test, after `sum` is deleted
+	let (number, _parse) = unsafe { callback::apply(mpc_tok(mpc_digits()), |s: &str| s.parse::<i64>().unwrap()) };
+	let (sum, _fold) = unsafe { callback::fold(number, |xs: Vec<i64>| xs.iter().sum::<i64>()) };
+	let input = CString::new("1 20 300").unwrap();
+	let filename = CString::new("<input>").unwrap();
+	match unsafe { glue::parse(&filename, &input, sum) }
+	{
+		Ok(val) => assert_eq!(*unsafe { Box::from_raw(val as *mut i64) }, 321),
+		Err(_) => panic!("sum failed to parse"),
+	}
+	unsafe { mpc_delete(sum); }
+
+	let vowel = p::satisfy(|c| b"aeiou".contains(&c)).many1();
+	assert_eq!(vowel.parse("<input>", "aeb").unwrap().to_ast().children.len(), 2);
+	assert!(vowel.parse("<input>", "b").is_err());
+
+	let word_end = p::alpha().many1().then(p::anchor(|_, next| next == b' ' || next == 0));
+	assert!(word_end.parse("<input>", "abc def").is_ok());
+	assert!(word_end.parse("<input>", "abc1").is_err());
+
+	// panics in closures fail the parse instead of unwinding into C
+	let quiet = p::satisfy(|c| if c == b'!' { panic!("no shouting") } else { c == b'a' }).many();
+	let error = quiet.parse("<input>", "aa!").err().unwrap();
+	assert_eq!(error.failure, Some("callback panicked: no shouting".to_owned()));
+	assert!(quiet.parse("<input>", "aa").is_ok());
+
+	let byte = typed::digits().map(|s| s.parse::<u8>().unwrap()).then(typed::char(b';')).map(|(b, _)| b);
+	let error = byte.parse("<input>", "300;").err().unwrap();
+	assert!(error.failure.unwrap().starts_with("callback panicked: "));
+	assert_eq!(byte.parse("<input>", "255;").unwrap(), 255);
+	assert_eq!(byte.parse("a\0b", "c").err().unwrap().filename, "a\\0b");
+
+	// predicates see bytes, not characters
+	let high = typed::satisfy(|b| b >= 0x80).many1();
+	assert_eq!(high.parse("<input>", "é").unwrap(), vec![0xc3, 0xa9]);
+	assert_eq!(typed::any().many().parse("<input>", "a\0").unwrap(), vec!['a', '\0']);
+	assert_eq!(typed::any().many().parse("<input>", "é€😀").unwrap(), vec!['é', '€', '😀']);
+}
+
+#[test]
+fn typed_parsers()
+{
+	let rules = p::Rules::new();
+	let expr = rules.typed_rule::<i64>("expr");
+	let term = rules.typed_rule::<i64>("term");
+	let factor = rules.typed_rule::<i64>("factor");
+
+	let number = typed::digits().map(|s| s.parse::<i64>().unwrap()).tok();
+	factor.define(number.or(expr.parser().between("(", ")").tok()));
+	term.define(factor.parser()
+		.then(typed::oneof("*/").tok().then(factor).many())
+		.map(|(first, rest)| rest.into_iter().fold(first, |x, (op, y)| if op == '*' { x * y } else { x / y })));
+	expr.define(term.parser()
+		.then(typed::oneof("+-").tok().then(term).many())
+		.map(|(first, rest)| rest.into_iter().fold(first, |x, (op, y)| if op == '+' { x + y } else { x - y })));
+
+	let calc = expr.parser().total();
+	assert_eq!(calc.parse("<input>", "2 * (3 + 4) - 5").unwrap(), 9);
+	assert_eq!(calc.parse("<input>", " 100 / 7 / 2 ").unwrap(), 7);
+	assert_eq!(calc.parse("<input>", "1 +").err().unwrap().expected, ["digits", "\"(\""]);
+
+	// values of partial matches are dropped when mpc backtracks
+	let live = Rc::new(Cell::new(0));
+	let counter = live.clone();
+	let tracked = typed::string("a").map(move |_| Tracked::new(&counter));
+	let pair = tracked.then(typed::string("b")).map(|(t, _)| vec![t]).or(typed::string("ac").map(|_| Vec::new()));
+	assert_eq!(pair.parse("<input>", "ac").unwrap().len(), 0);
+	assert_eq!(pair.parse("<input>", "ab").unwrap().len(), 1);
+	assert!(pair.parse("<input>", "ad").is_err());
+	assert_eq!(live.get(), 0);
+
+	let keyword = (!typed::digits()).then(typed::ident()).map(|(_, s)| s);
+	assert_eq!(keyword.parse("<input>", "let").unwrap(), "let");
+	assert!(keyword.parse("<input>", "1et").is_err());
+
+	let optional = typed::ident().maybe().skip(typed::char(b';'));
+	assert_eq!(optional.parse("<input>", "x;").unwrap(), Some("x".to_owned()));
+	assert_eq!(optional.parse("<input>", ";").unwrap(), None);
+
+	let outer = p::Rules::new();
+	let count = outer.typed_rule::<usize>("count");
+	{
+		let inner = p::Rules::new();
+		let digit = inner.typed_rule::<char>("digit");
+		digit.define(typed::oneof("0123456789"));
+		count.define(digit.parser().many1().map(|ds| ds.len()));
+		assert_eq!(count.parse("<input>", "123").unwrap(), 3);
+	}
+	drop(outer);
+}
+
+#[test]
+fn streamed_input()
+{
+	let language = sentence_grammar();
+	let input = "Bananas are awesome. What do you mean, potato?";
+
+	let path = ::std::env::temp_dir().join(format!("mpc-streamed-input-{}.txt", ::std::process::id()));
+	fs::write(&path, input).unwrap();
+	let from_file = language.parse_file(&path).unwrap();
+	fs::write(&path, "").unwrap();
+	match language.parse_file(&path)
+	{
+		Err(ReadError::Parse(e)) => assert_eq!(e.received, '\0'),
+		_ => panic!(),
+	}
+	fs::remove_file(&path).unwrap();
+	match language.parse_file(&path)
+	{
+		Err(ReadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+		_ => panic!(),
+	}
+
+	// one byte at a time, like a slow socket
+	let from_reader = language.parse_reader("<pipe>", Trickle(input.as_bytes())).unwrap();
+	assert!(from_reader == from_file);
+	assert!(from_file == language.parse("<input>", input).unwrap());
+
+	let error = language.parse_reader("<pipe>", "Bananas are".as_bytes()).err().unwrap();
+	match error
+	{
+		ReadError::Parse(ref e) => assert_eq!((e.filename.as_str(), e.column), ("<pipe>", 11)),
+		_ => panic!(),
+	}
+
+	match language.parse_reader("<pipe>", Trickle(b"Bananas are").chain(Broken))
+	{
+		Err(ReadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+		_ => panic!(),
+	}
+
+	// a failed read ends the input, even where any byte would match
+	let records = Grammar::new("record : /[^;]*/ ';' ; log : /^/ <record>* /$/ ;")
+		.main("log")
+		.rule("record")
+		.build()
+		.unwrap();
+	match records.parse_reader("<pipe>", Broken)
+	{
+		Err(ReadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+		_ => panic!(),
+	}
+
+	// only the bytes that fit the buffer are used
+	let tree = language.parse_reader("<pipe>", Boastful(b"Bananas!")).unwrap();
+	assert_eq!(tree.root().span(), Span { start: 0, end: 8 });
+}
+
+/// Reads one byte at a time
+struct Trickle<'a>(&'a [u8]);
+
+impl<'a> Read for Trickle<'a>
+{
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+	{
+		let n = ::std::cmp::min(1, buf.len()).min(self.0.len());
+		buf[..n].copy_from_slice(&self.0[..n]);
+		self.0 = &self.0[n..];
+		Ok(n)
+	}
+}
+
+/// Claims to have read more than the buffer holds, once
+struct Boastful<'a>(&'a [u8]);
+
+impl<'a> Read for Boastful<'a>
+{
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+	{
+		let n = self.0.len().min(buf.len());
+		buf[..n].copy_from_slice(&self.0[..n]);
+		self.0 = &self.0[n..];
+		Ok(if n > 0 { buf.len() + 64 } else { 0 })
+	}
+}
+
+/// Always fails to read
+struct Broken;
+
+impl Read for Broken
+{
+	fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize>
+	{
+		Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+	}
+}
+
+#[test]
+fn byte_input()
+{
+	let language = Grammar::new("record : /[^;]*/ ';' ; log : /^/ <record>* /$/ ;")
+		.main("log")
+		.rule("record")
+		.build()
+		.unwrap();
+
+	// nul bytes don't end the input
+	let tree = language.parse_bytes("<log>", b"ab\0cd;ef;").unwrap();
+	assert_eq!(tree.root().children_num(), 4);
+	let record = tree.root().child(1).unwrap().child(0).unwrap();
+	assert_eq!(record.contents(), Ok("ab\0cd"));
+	assert_eq!(record.span(), Span { start: 0, end: 5 });
+	let ast = tree.to_ast();
+	assert_eq!(ast.children[1].children[0].contents, "ab\0cd");
+	assert!(ast.to_raw() == tree);
+	assert!(language.parse("<log>", "ab\0cd;ef;").unwrap() == tree);
+	let tree = language.parse_bytes("<log>", b"\0\0;").unwrap();
+	assert_eq!(tree.root().child(1).unwrap().child(0).unwrap().contents_bytes(), b"\0\0");
+
+	// only `length` bytes are parsed
+	let tree = language.parse_bytes("<log>", &b"ab;cd"[..3]).unwrap();
+	assert_eq!(tree.try_into_ast().unwrap().children.len(), 3);
+
+	let tree = language.parse_bytes("<log>", b"ok;a\xffb;").unwrap();
+	let error = tree.try_into_ast().err().unwrap();
+	assert_eq!((error.tag.as_str(), error.column, error.error.valid_up_to()), ("regex", 3, 1));
+	traceln!(error);
+
+	let error = language.parse_bytes("<log>", b"ab;c\0d").err().unwrap();
+	assert_eq!((error.position, error.received), (6, '\0'));
+
+	// so do filenames, escaped in errors
+	assert_eq!(language.parse_bytes("a\0b", b"c").err().unwrap().filename, "a\\0b");
+}
+
+#[test]
+fn owned_c_strings()
+{
+	// Strings handed to C must outlive the call. scripts/asan.sh runs the
+	// tests under AddressSanitizer, which fails on any use after free
+	let parsers = parser!
+	{
+		grammar: {"word : /[a-z]+/; sentence : <word>+ '.';"}
+		main: sentence
+		parsers: word
+	}.unwrap();
+
+	for i in 0..8
+	{
+		// freshly allocated each time, so a dangling pointer reads freed memory
+		let input = format!("{}.", vec!["abc"; i + 1].join(" "));
+		let filename = format!("input{}.txt", i);
+		let tree = run_parser!
+		{
+			preparsers: parsers
+			filename: {filename.as_str()}
+			input: {input.as_str()}
+		};
+		assert_eq!(tree.ok().unwrap().into_ast().children.len(), i + 2);
+
+		let error = run_parser!
+		{
+			preparsers: parsers
+			filename: {filename.clone()}
+			input: {input.replace(".", "!")}
+		};
+		assert_eq!(error.err().unwrap().filename, filename);
+	}
+
+	let error = unsafe { ParseError::from_raw(mpc_err_t::new(&format!("{}!", "failed"))) };
+	assert_eq!((error.filename.as_str(), error.failure), ("<input>", Some("failed!".to_owned())));
+}
+
+#[test]
+fn prepared_language()
+{
+	let language = sentence_grammar();
+	assert_eq!(language.main_rule().name(), "paragraph");
+
+	let input = "Bananas are awesome. What do you mean?";
+	assert_eq!(language.parse_str(input).unwrap().into_ast().children.len(), 2);
+
+	let sentence = language.rule("sentence").unwrap();
+	let tree = sentence.parse("sentence.txt", "What do you mean?").unwrap();
+	assert_eq!(tree.root().tag(), ">");
+	assert_eq!(tree.root().children_num(), 5);
+	assert_eq!(language.rule("word").unwrap().parse_str("potato").unwrap().root().contents(), Ok("potato"));
+
+	let path = ::std::env::temp_dir().join(format!("mpc-prepared-language-{}.txt", ::std::process::id()));
+	fs::write(&path, input).unwrap();
+	let from_file = run_parser!
+	{
+		preparsers: language
+		filename: {&path}
+	};
+	fs::remove_file(&path).unwrap();
+	assert!(from_file.unwrap() == language.parse_str(input).unwrap());
+}
+
+#[test]
+fn unknown_rule()
+{
+	let language = Grammar::new("word : /[a-z]+/;").main("word").build().unwrap();
+	assert!(language.rule("verb").is_none());
+	assert_eq!(language.rule("word").unwrap().name(), "word");
+}
+
+#[test]
+fn rule_entry_points()
+{
+	let language = sentence_grammar();
+	// the same production compiled as the main rule of its own grammar
+	let sentence = parser!
+	{
+		grammar:
+		{g_string![
+			"word : /[a-zA-Z0-9]+/;                         \n"
+			"punct: '.' | '!' | ',' | ';' | '?' | '-' | ':';\n"
+			"sentence: <word>+ <punct>;                     \n"
+		]}
+		main: sentence
+		parsers: word punct
+	}.unwrap();
+
+	for input in &["Bananas are awesome.", "What do you mean?"]
+	{
+		let tree = run_parser!
+		{
+			preparsers: language
+			rule: sentence
+			filename: {"test.txt"}
+			input: {input}
+		};
+		assert!(tree.unwrap() == sentence.parse("test.txt", input).unwrap());
+	}
+
+	for input in &["Bananas are", "Bananas are awesome", "?"]
+	{
+		let error = run_parser!
+		{
+			preparsers: language
+			rule: sentence
+			input: {input}
+		};
+		let expected = sentence.parse_str(input).err().unwrap();
+		assert_eq!(error.err().unwrap().to_string(), expected.to_string());
+	}
+
+	let punct = run_parser!
+	{
+		preparsers: language
+		rule: punct
+		input: {"!"}
+	};
+	assert_eq!(punct.unwrap().into_ast(), ast!("char" => "!"));
+
+	let unknown = run_parser!
+	{
+		preparsers: language
+		rule: verb
+		filename: {"test.txt"}
+		input: {"run"}
+	};
+	let error = unknown.err().unwrap();
+	assert_eq!(error.filename, "test.txt");
+	assert_eq!(error.failure, Some("no rule named 'verb'".to_owned()));
+
+	let unknown = run_parser!
+	{
+		preparsers: language
+		rule: verb
+		filename: {"missing.txt"}
+	};
+	match unknown
+	{
+		Err(ReadError::Parse(e)) => assert_eq!(e.failure, Some("no rule named 'verb'".to_owned())),
+		_ => panic!("expected a failure for an unknown rule"),
+	}
+}
+
+/// Counts its live instances
+struct Tracked(Rc<Cell<i32>>);
+
+impl Tracked
+{
+	fn new(live: &Rc<Cell<i32>>) -> Tracked
+	{
+		live.set(live.get() + 1);
+		Tracked(live.clone())
+	}
+}
+
+impl Drop for Tracked
+{
+	fn drop(&mut self)
+	{
+		self.0.set(self.0.get() - 1);
+	}
+}
@@ -0,0 +1,388 @@
+//! Contains an owner of a raw `mpc_ast_t` tree and a zero-copy view into it
+#![allow(dead_code)]
+
+use ast::{Ast, Span};
+use mpc_c::*;
+use mpc_c_types::*;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::slice;
+use std::str::{self, Utf8Error};
+
+/// Owner of a tree produced by mpc. The tree is deleted
+/// using `mpc_ast_delete` when the `ParseTree` is dropped
+pub struct ParseTree
+{
+	raw: *mut mpc_ast_t,
+}
+
+impl ParseTree
+{
+	/// Take ownership of a raw tree, such as the output of `glue::parse`
+	///
+	/// # Safety
+	///
+	/// `raw` must be a tree allocated by mpc, it is deleted with
+	/// `mpc_ast_delete` on drop. Nothing else may delete it afterwards
+	pub unsafe fn from_raw(raw: *mut mpc_ast_t) -> ParseTree
+	{
+		ParseTree { raw: raw }
+	}
+
+	/// Pointer to the underlying tree
+	pub fn as_ptr(&self) -> *mut mpc_ast_t
+	{
+		self.raw
+	}
+
+	/// Give up ownership of the tree without deleting it
+	pub fn into_raw(self) -> *mut mpc_ast_t
+	{
+		let raw = self.raw;
+		mem::forget(self);
+		raw
+	}
+
+	/// Borrowing view of the root node
+	pub fn root(&self) -> AstRef<'_>
+	{
+		unsafe { AstRef::from_raw(self.raw) }
+	}
+
+	/// Copy the tree into an owned `Ast`. Its `raw_ast` pointers
+	/// stay valid for as long as this `ParseTree` lives
+	pub fn to_ast(&self) -> Ast
+	{
+		Ast::new(self.raw)
+	}
+
+	/// Copy the tree into an owned `Ast` and delete the raw tree.
+	/// `raw_ast` of all nodes is set to a nullptr
+	pub fn into_ast(self) -> Ast
+	{
+		let mut ast = Ast::new(self.raw);
+		ast.forget_raw();
+		ast
+	}
+
+	/// Like `into_ast`, but fails instead of replacing contents
+	/// which are not valid UTF-8, like those of `parse_bytes`
+	pub fn try_into_ast(self) -> Result<Ast, ContentsError>
+	{
+		for (_, node) in self.root().iter_preorder()
+		{
+			if let Err(e) = node.contents()
+			{
+				return Err(ContentsError
+				{
+					tag: node.tag().to_owned(),
+					row: node.row(),
+					column: node.column(),
+					position: node.position(),
+					error: e,
+				});
+			}
+		}
+		Ok(self.into_ast())
+	}
+}
+
+/// Error returned for a node whose contents are not valid UTF-8
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContentsError
+{
+	/// Tag of the node
+	pub tag: String,
+	/// Row of the node
+	pub row: usize,
+	/// Column of the node
+	pub column: usize,
+	/// Position of the node in the input
+	pub position: usize,
+	/// Where in the contents decoding failed
+	pub error: Utf8Error,
+}
+
+impl fmt::Display for ContentsError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "contents of '{}' at {}:{} are not valid UTF-8: {}", self.tag, self.row + 1, self.column + 1, self.error)
+	}
+}
+
+impl Error for ContentsError
+{
+	fn source(&self) -> Option<&(dyn Error + 'static)>
+	{
+		Some(&self.error)
+	}
+}
+
+impl PartialEq for ParseTree
+{
+	/// Trees are compared using `mpc_ast_eq`, see `AstRef::eq`
+	fn eq(&self, other: &ParseTree) -> bool
+	{
+		self.root() == other.root()
+	}
+}
+
+impl Drop for ParseTree
+{
+	fn drop(&mut self)
+	{
+		unsafe { mpc_ast_delete(self.raw); }
+	}
+}
+
+/// A node of a raw `mpc_ast_t` tree, borrowed in place without copying
+#[derive(Clone, Copy)]
+pub struct AstRef<'a>
+{
+	raw: *const mpc_ast_t,
+	tree: PhantomData<&'a mpc_ast_t>,
+}
+
+impl<'a> AstRef<'a>
+{
+	/// View a raw node
+	///
+	/// # Safety
+	///
+	/// `raw` must point to a valid `mpc_ast_t`, which stays alive
+	/// and unmodified for the lifetime `'a`
+	pub unsafe fn from_raw(raw: *const mpc_ast_t) -> AstRef<'a>
+	{
+		AstRef
+		{
+			raw: raw,
+			tree: PhantomData,
+		}
+	}
+
+	/// Pointer to the underlying node
+	pub fn as_ptr(&self) -> *const mpc_ast_t
+	{
+		self.raw
+	}
+
+	/// Tag of this node, see `Ast::tag`
+	pub fn tag(&self) -> &'a str
+	{
+		// tags are made of rule names and mpc's own tags, both valid UTF-8
+		unsafe { str::from_utf8_unchecked(CStr::from_ptr((*self.raw).tag).to_bytes()) }
+	}
+
+	/// Raw contents of this node, nul bytes included
+	pub fn contents_bytes(&self) -> &'a [u8]
+	{
+		unsafe { slice::from_raw_parts((*self.raw).contents as *const u8, (*self.raw).contents_len as usize) }
+	}
+
+	/// Contents of this node. Fails if a parser split
+	/// a multi-byte character, e.g. a regex matching `.`
+	pub fn contents(&self) -> Result<&'a str, Utf8Error>
+	{
+		str::from_utf8(self.contents_bytes())
+	}
+
+	/// Number of the row where this node is located, only valid
+	/// for nodes without children
+	pub fn row(&self) -> usize
+	{
+		unsafe { dfu!(self.raw, state.row) }
+	}
+
+	/// Number of the column where this node is located, only valid
+	/// for nodes without children
+	pub fn column(&self) -> usize
+	{
+		unsafe { dfu!(self.raw, state.col) }
+	}
+
+	/// Total position in the file, only valid for nodes with no children
+	pub fn position(&self) -> usize
+	{
+		unsafe { dfu!(self.raw, state.pos) }
+	}
+
+	/// Bytes of the input covered by this node, see `Ast::span`
+	pub fn span(&self) -> Span
+	{
+		// leaves are ordered, so the node spans from its
+		// leftmost leaf to its rightmost one
+		let mut first = *self;
+		while let Some(child) = first.children().next()
+		{
+			first = child;
+		}
+
+		let mut last = *self;
+		while let Some(child) = last.children().next_back()
+		{
+			last = child;
+		}
+
+		Span
+		{
+			start: first.position(),
+			end: last.position() + last.contents_bytes().len(),
+		}
+	}
+
+	/// The part of `input` covered by this node, see `Ast::source_text`
+	pub fn source_text<'b>(&self, input: &'b str) -> &'b str
+	{
+		let span = self.span();
+		&input[span.start..span.end]
+	}
+
+	/// Number of child nodes
+	pub fn children_num(&self) -> usize
+	{
+		unsafe { dfu!(self.raw, children_num) }
+	}
+
+	/// Child at `index`
+	pub fn child(&self, index: usize) -> Option<AstRef<'a>>
+	{
+		self.children().nth(index)
+	}
+
+	/// Iterate over the child nodes
+	pub fn children(&self) -> Children<'a>
+	{
+		let nodes: &'a [*mut mpc_ast_t] =
+			if self.children_num() == 0 { &[] }
+			else { unsafe { slice::from_raw_parts((*self.raw).children, self.children_num()) } };
+
+		Children { nodes: nodes.iter() }
+	}
+
+	/// Copy this node and all its descendants into an owned `Ast`
+	pub fn to_ast(&self) -> Ast
+	{
+		Ast::new(self.raw as *mut mpc_ast_t)
+	}
+
+	/// Iterate over this node and its descendants in pre-order using
+	/// mpc's traversal, yielding each node with its depth below this one
+	pub fn iter_preorder(&self) -> Traversal<'a>
+	{
+		Traversal::new(self.raw, mpc_ast_trav_order_t::mpc_ast_trav_order_pre)
+	}
+
+	/// Iterate over this node and its descendants in post-order using
+	/// mpc's traversal, yielding each node with its depth below this one
+	pub fn iter_postorder(&self) -> Traversal<'a>
+	{
+		Traversal::new(self.raw, mpc_ast_trav_order_t::mpc_ast_trav_order_post)
+	}
+}
+
+impl<'a, 'b> PartialEq<AstRef<'b>> for AstRef<'a>
+{
+	/// Nodes are compared using `mpc_ast_eq`, which compares tags,
+	/// contents and children, but not locations
+	fn eq(&self, other: &AstRef<'b>) -> bool
+	{
+		unsafe { mpc_ast_eq(self.raw as *mut mpc_ast_t, other.raw as *mut mpc_ast_t) != 0 }
+	}
+}
+
+/// Iterator over a raw tree driven by `mpc_ast_traverse_next`
+pub struct Traversal<'a>
+{
+	trav: *mut mpc_ast_trav_t,
+	tree: PhantomData<&'a mpc_ast_t>,
+}
+
+impl<'a> Traversal<'a>
+{
+	fn new(raw: *const mpc_ast_t, order: mpc_ast_trav_order_t) -> Traversal<'a>
+	{
+		Traversal
+		{
+			trav: unsafe { mpc_ast_traverse_start(raw as *mut mpc_ast_t, order) },
+			tree: PhantomData,
+		}
+	}
+}
+
+impl<'a> Iterator for Traversal<'a>
+{
+	type Item = (usize, AstRef<'a>);
+
+	fn next(&mut self) -> Option<(usize, AstRef<'a>)>
+	{
+		if self.trav.is_null()
+		{
+			return None;
+		}
+
+		unsafe
+		{
+			// the node returned next is the current one, its depth
+			// is the number of traversal frames above it
+			let mut depth = 0;
+			let mut frame = (*self.trav).parent;
+			while !frame.is_null()
+			{
+				depth += 1;
+				frame = (*frame).parent;
+			}
+
+			let node = mpc_ast_traverse_next(&mut self.trav);
+			if node.is_null() { None }
+			else { Some((depth, AstRef::from_raw(node))) }
+		}
+	}
+}
+
+impl<'a> Drop for Traversal<'a>
+{
+	fn drop(&mut self)
+	{
+		unsafe { mpc_ast_traverse_free(&mut self.trav); }
+	}
+}
+
+/// Iterator over the children of an `AstRef`
+pub struct Children<'a>
+{
+	nodes: slice::Iter<'a, *mut mpc_ast_t>,
+}
+
+impl<'a> Iterator for Children<'a>
+{
+	type Item = AstRef<'a>;
+
+	fn next(&mut self) -> Option<AstRef<'a>>
+	{
+		self.nodes.next().map(|node| unsafe { AstRef::from_raw(*node) })
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>)
+	{
+		self.nodes.size_hint()
+	}
+
+	fn nth(&mut self, n: usize) -> Option<AstRef<'a>>
+	{
+		self.nodes.nth(n).map(|node| unsafe { AstRef::from_raw(*node) })
+	}
+}
+
+impl<'a> DoubleEndedIterator for Children<'a>
+{
+	fn next_back(&mut self) -> Option<AstRef<'a>>
+	{
+		self.nodes.next_back().map(|node| unsafe { AstRef::from_raw(*node) })
+	}
+}
+
+impl<'a> ExactSizeIterator for Children<'a> {}
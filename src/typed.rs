@@ -0,0 +1,396 @@
+//! Contains parsers producing Rust values instead of an Ast. Values
+//! are carried through mpc as boxes and dropped when mpc backtracks,
+//! so `parse` returns the value built by the parser directly.
+//!
+//! ```rust
+//! # extern crate mpc;
+//! use mpc::typed;
+//! # fn main() {
+//! let number = typed::digits().map(|s| s.parse::<i64>().unwrap()).tok();
+//! let sum = number.many1().map(|xs| xs.iter().sum::<i64>()).total();
+//!
+//! assert_eq!(sum.parse("<input>", "1 20 300").unwrap(), 321);
+//! assert!(sum.parse("<input>", "99999999999999999999999").is_err());
+//! # }
+//! ```
+//!
+//! Closures may panic, the parse then fails with the panic's message.
+//! Functions taking strings panic if they contain a nul byte, except
+//! for the input of `parse`.
+#![allow(dead_code)]
+
+use callback::{self, Callback};
+use combinators::{Invariant, Owned, Rules};
+use error::ParseError;
+use glue;
+use mpc_c::*;
+use mpc_c_types::*;
+use parser::Parser as RawParser;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Not;
+use std::os::raw::c_char;
+use std::str;
+
+/// A parser producing a `T`, `'a` being the lifetime of the `Rules`
+/// it refers to. Freed when dropped unless consumed by another one
+pub struct Parser<'a, T>
+{
+	raw: parser_ptr,
+	owned: Owned,
+	rules: Invariant<'a>,
+	value: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Parser<'a, T>
+{
+	/// Take ownership of a raw parser producing a `Box<T>` as its `mpc_val_t`
+	///
+	/// # Safety
+	///
+	/// Every output of `raw` must be a `Box<T>`, never a nullptr unless
+	/// a callback panicked. It is deleted with `mpc_delete` on drop, so
+	/// nothing else may free it, and the strings and callbacks it refers
+	/// to must outlive it
+	pub unsafe fn from_raw(raw: parser_ptr) -> Parser<'a, T>
+	{
+		Parser
+		{
+			raw: raw,
+			owned: Owned::default(),
+			rules: PhantomData,
+			value: PhantomData,
+		}
+	}
+
+	/// Pointer to the underlying parser
+	pub fn as_ptr(&self) -> parser_ptr
+	{
+		self.raw
+	}
+
+	/// Give up the parser along with what it points to
+	fn into_parts(mut self) -> (parser_ptr, Owned)
+	{
+		let parts = (self.raw, mem::take(&mut self.owned));
+		mem::forget(self);
+		parts
+	}
+
+	/// Wrap the parser into the one returned by `f`, producing a `U`
+	fn wrap<U, F>(self, f: F) -> Parser<'a, U>
+		where F: FnOnce(parser_ptr) -> parser_ptr
+	{
+		let (raw, owned) = self.into_parts();
+		Parser
+		{
+			raw: f(raw),
+			owned: owned,
+			rules: PhantomData,
+			value: PhantomData,
+		}
+	}
+
+	fn with(mut self, owned: &mut Owned) -> Parser<'a, T>
+	{
+		self.owned.append(owned);
+		self
+	}
+
+	fn with_callback(self, callback: Callback) -> Parser<'a, T>
+	{
+		self.with(&mut Owned { callbacks: vec![callback], ..Owned::default() })
+	}
+
+	/// Transform the value with `f`
+	pub fn map<U, F>(self, f: F) -> Parser<'a, U>
+		where F: Fn(T) -> U + 'static
+	{
+		let callback = Callback::new(f);
+		let x = callback.as_ptr();
+		self.wrap(|raw| unsafe { mpc_apply_to(raw, callback::apply_boxed::<F, T, U>, x) })
+			.with_callback(callback)
+	}
+
+	/// Run this parser and then `other`, producing both values
+	pub fn then<U, P: Into<Parser<'a, U>>>(self, other: P) -> Parser<'a, (T, U)>
+	{
+		let (other, mut owned) = other.into().into_parts();
+		self.wrap(|raw| unsafe
+			{
+				mpc_and(2, callback::fold_pair::<T, U>, raw, other, callback::drop_boxed::<T> as mpc_dtor_t)
+			})
+			.with(&mut owned)
+	}
+
+	/// Run this parser and then `other`, keeping only the value of this one
+	pub fn skip<U: 'static, P: Into<Parser<'a, U>>>(self, other: P) -> Parser<'a, T>
+		where T: 'static
+	{
+		self.then(other).map(|(t, _)| t)
+	}
+
+	/// Run this parser or `other` if it fails
+	pub fn or<P: Into<Parser<'a, T>>>(self, other: P) -> Parser<'a, T>
+	{
+		let (other, mut owned) = other.into().into_parts();
+		self.wrap(|raw| unsafe { mpc_or(2, raw, other) }).with(&mut owned)
+	}
+
+	/// Run this parser zero or more times
+	pub fn many(self) -> Parser<'a, Vec<T>>
+	{
+		self.wrap(|raw| unsafe { mpc_many(callback::fold_vec::<T>, raw) })
+	}
+
+	/// Run this parser one or more times
+	pub fn many1(self) -> Parser<'a, Vec<T>>
+	{
+		self.wrap(|raw| unsafe { mpc_many1(callback::fold_vec::<T>, raw) })
+	}
+
+	/// Run this parser exactly `n` times
+	pub fn count(self, n: usize) -> Parser<'a, Vec<T>>
+	{
+		self.wrap(|raw| unsafe { mpc_count(n as i32, callback::fold_vec::<T>, raw, callback::drop_boxed::<T>) })
+	}
+
+	/// Run this parser, succeeding with `None` if it fails
+	pub fn maybe(self) -> Parser<'a, Option<T>>
+	{
+		self.wrap(|raw| unsafe { mpc_apply(mpc_maybe(raw), callback::apply_option::<T>) })
+	}
+
+	/// Skip whitespace following this parser
+	pub fn tok(self) -> Parser<'a, T>
+	{
+		self.wrap(|raw| unsafe
+		{
+			mpc_and(2, mpcf_fst_free, raw, mpc_whitespaces(), callback::drop_boxed::<T> as mpc_dtor_t)
+		})
+	}
+
+	/// Run this parser between the strings `open` and `close`
+	pub fn between(self, open: &str, close: &str) -> Parser<'a, T>
+	{
+		let open = CString::new(open).unwrap();
+		let close = CString::new(close).unwrap();
+		self.wrap(|raw| unsafe { mpc_between(raw, callback::drop_boxed::<T>, open.as_ptr(), close.as_ptr()) })
+	}
+
+	/// Match the whole input, leading and trailing whitespace included
+	pub fn total(self) -> Parser<'a, T>
+	{
+		self.wrap(|raw| unsafe { mpc_total(raw, callback::drop_boxed::<T>) })
+	}
+
+	/// Report `expected` instead of the parser's own expectations on failure
+	pub fn expect(self, expected: &str) -> Parser<'a, T>
+	{
+		let expected = CString::new(expected).unwrap();
+		self.wrap(|raw| unsafe { mpc_expect(raw, expected.as_ptr()) })
+	}
+
+	/// Parse `input`, `filename` is used in error messages
+	pub fn parse(&self, filename: &str, input: &str) -> Result<T, ParseError>
+	{
+		let c_filename = glue::filename(filename);
+		callback::take_panic();
+		let res = unsafe { glue::nparse(&c_filename, input.as_bytes(), self.raw) };
+		match callback::take_panic()
+		{
+			Some(message) =>
+			{
+				// the value is incomplete, or a nullptr
+				match res
+				{
+					Ok(val) => unsafe { callback::drop_boxed::<T>(val as *mut mpc_val_t) },
+					Err(e) => drop(unsafe { ParseError::from_raw(e) }),
+				}
+				Err(callback::panic_error(filename, &message))
+			},
+			None => match res
+			{
+				Ok(val) => Ok(unsafe { *Box::from_raw(val as *mut T) }),
+				Err(e) => Err(unsafe { ParseError::from_raw(e) }),
+			},
+		}
+	}
+}
+
+/// `!p` succeeds with `()` without consuming input only if `p` fails
+impl<'a, T> Not for Parser<'a, T>
+{
+	type Output = Parser<'a, ()>;
+
+	fn not(self) -> Parser<'a, ()>
+	{
+		self.wrap(|raw| unsafe { mpc_not_lift(raw, callback::drop_boxed::<T>, callback::lift_unit) })
+	}
+}
+
+impl<'a, T> Drop for Parser<'a, T>
+{
+	fn drop(&mut self)
+	{
+		// retained rules referred to are skipped by mpc_delete
+		unsafe { mpc_delete(self.raw); }
+	}
+}
+
+/// Box the string produced by a primitive parser
+fn string_of<'a>(raw: parser_ptr) -> Parser<'a, String>
+{
+	unsafe { Parser::from_raw(mpc_apply(raw, callback::apply_string)) }
+}
+
+/// Box the character produced by a primitive parser
+fn char_of<'a>(raw: parser_ptr) -> Parser<'a, char>
+{
+	unsafe { Parser::from_raw(mpc_apply(raw, callback::apply_char)) }
+}
+
+/// Match any character, or a single byte where the input isn't valid UTF-8
+pub fn any<'a>() -> Parser<'a, char>
+{
+	// a leading byte in `lo..=hi` followed by `n` continuation bytes
+	let sequence = |lo: u8, hi: u8, n: usize| satisfy(move |b| lo <= b && b <= hi)
+		.then(satisfy(|b| b & 0xc0 == 0x80).count(n))
+		.map(|(b, mut rest)| { rest.insert(0, b); rest });
+
+	sequence(0xf0, 0xf4, 3)
+		.or(sequence(0xe0, 0xef, 2))
+		.or(sequence(0xc2, 0xdf, 1))
+		.or(satisfy(|_| true).map(|b| vec![b]))
+		.map(|bytes| str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).unwrap_or('\u{fffd}'))
+}
+
+/// Match the ASCII character `c`
+pub fn char<'a>(c: u8) -> Parser<'a, char> { char_of(unsafe { mpc_char(c as c_char) }) }
+
+/// Match one of the bytes of `chars`
+pub fn oneof<'a>(chars: &str) -> Parser<'a, char>
+{
+	let chars = CString::new(chars).unwrap();
+	char_of(unsafe { mpc_oneof(chars.as_ptr()) })
+}
+
+/// Match a byte for which `f` returns true, producing the byte. Bytes are
+/// passed one at a time, so a multi-byte UTF-8 character takes several of them
+pub fn satisfy<'a, F>(f: F) -> Parser<'a, u8>
+	where F: Fn(u8) -> bool + 'static
+{
+	// the callback is kept by the returned parser
+	let (raw, callback) = unsafe { callback::satisfy_with(f) };
+	unsafe { Parser::from_raw(mpc_apply(raw, callback::apply_byte)) }.with_callback(callback)
+}
+
+/// Match the string `s`
+pub fn string<'a>(s: &str) -> Parser<'a, String>
+{
+	let s = CString::new(s).unwrap();
+	string_of(unsafe { mpc_string(s.as_ptr()) })
+}
+
+/// Match the regular expression `re`, with mpc's syntax
+pub fn regex<'a>(re: &str) -> Parser<'a, String>
+{
+	let re = CString::new(re).unwrap();
+	string_of(unsafe { mpc_re(re.as_ptr()) })
+}
+
+/// Match one or more digits
+pub fn digits<'a>() -> Parser<'a, String> { string_of(unsafe { mpc_digits() }) }
+/// Match a floating point number
+pub fn real<'a>() -> Parser<'a, String> { string_of(unsafe { mpc_real() }) }
+/// Match a valid C identifier
+pub fn ident<'a>() -> Parser<'a, String> { string_of(unsafe { mpc_ident() }) }
+
+/// A named parser of `Rules` producing a `T`, see `Rules::typed_rule`
+pub struct Rule<'a, T>
+{
+	raw: parser_ptr,
+	rules: &'a Rules,
+	lifetime: Invariant<'a>,
+	value: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Clone for Rule<'a, T>
+{
+	fn clone(&self) -> Rule<'a, T>
+	{
+		*self
+	}
+}
+
+impl<'a, T> Copy for Rule<'a, T> {}
+
+impl<'a, T> Rule<'a, T>
+{
+	/// Define this rule as `parser`, replacing any previous definition.
+	/// Other `Rules` are kept alive the same way as by `Rules::define`
+	pub fn define(self, parser: Parser<'a, T>)
+	{
+		let (raw, owned) = parser.into_parts();
+		unsafe
+		{
+			mpc_undefine(self.raw);
+			mpc_define(self.raw, raw);
+		}
+		self.rules.keep(owned);
+	}
+
+	/// Refer to this rule
+	pub fn parser(self) -> Parser<'a, T>
+	{
+		self.into()
+	}
+
+	/// Parse `input` using this rule, see `Parser::parse`
+	pub fn parse(self, filename: &str, input: &str) -> Result<T, ParseError>
+	{
+		self.parser().parse(filename, input)
+	}
+}
+
+impl<'a, T> From<Rule<'a, T>> for Parser<'a, T>
+{
+	fn from(rule: Rule<'a, T>) -> Parser<'a, T>
+	{
+		// wrapped, so that the retained rule isn't deleted with the parser
+		let parser = unsafe { Parser::from_raw(mpc_apply(rule.raw, callback::identity)) };
+		parser.with(&mut Owned { rules: vec![rule.rules.set.clone()], ..Owned::default() })
+	}
+}
+
+impl Rules
+{
+	/// Create an undefined rule called `name` producing a `T`
+	///
+	/// ```rust
+	/// # extern crate mpc;
+	/// use mpc::combinators::Rules;
+	/// use mpc::typed;
+	/// # fn main() {
+	/// let rules = Rules::new();
+	/// let depth = rules.typed_rule::<usize>("depth");
+	/// depth.define(depth.parser().between("(", ")").map(|d| d + 1).or(typed::string("x").map(|_| 0)));
+	///
+	/// assert_eq!(depth.parse("<input>", "((x))").unwrap(), 2);
+	/// # }
+	/// ```
+	pub fn typed_rule<T>(&self, name: &str) -> Rule<'_, T>
+	{
+		let parser = RawParser::new(name);
+		let rule = Rule
+		{
+			raw: parser.as_ptr(),
+			rules: self,
+			lifetime: PhantomData,
+			value: PhantomData,
+		};
+		self.set.parsers.borrow_mut().push(parser);
+		rule
+	}
+}
@@ -0,0 +1,185 @@
+//! Contains traits for walking and transforming an `Ast`
+#![allow(dead_code)]
+
+use ast::Ast;
+use std::mem;
+use std::vec;
+
+/// Walks an Ast, see `Ast::walk`
+pub trait Visitor
+{
+	/// Called before the children of `ast` are visited
+	fn enter(&mut self, _ast: &Ast) {}
+	/// Called after the children of `ast` were visited
+	fn leave(&mut self, _ast: &Ast) {}
+}
+
+/// Rebuilds an Ast bottom-up, see `Ast::fold`
+pub trait Folder
+{
+	/// Transform `ast`, whose children have already been folded.
+	/// Returns the node unchanged by default
+	fn fold(&mut self, ast: Ast) -> Ast
+	{
+		ast
+	}
+}
+
+/// A `Visitor` dispatching nodes to closures by tag. Folded tags
+/// are dispatched to the closures of each of their components
+///
+/// ```rust
+/// # extern crate mpc;
+/// # use mpc::grammar::Grammar;
+/// # use mpc::visit::TagVisitor;
+/// # fn main() {
+/// let language = Grammar::new("word : /[a-z]+/; sentence : <word>+ '.'; text : <sentence>+;")
+///     .main("text")
+///     .rule("word")
+///     .rule("sentence")
+///     .build()
+///     .unwrap();
+/// let ast = language.parse("<input>", "bananas are great. yes.").unwrap().into_ast();
+///
+/// let mut words = 0;
+/// let mut sentences = 0;
+/// {
+///     let mut visitor = TagVisitor::new()
+///         .visit_tag("word", |_| words += 1)
+///         .leave_tag("sentence", |_| sentences += 1);
+///     ast.walk(&mut visitor);
+/// }
+/// assert_eq!((words, sentences), (4, 2));
+/// # }
+/// ```
+pub struct TagVisitor<'a>
+{
+	enter: Vec<(String, TagCallback<'a>)>,
+	leave: Vec<(String, TagCallback<'a>)>,
+}
+
+/// A closure called by `TagVisitor` on the nodes of a tag
+type TagCallback<'a> = Box<dyn FnMut(&Ast) + 'a>;
+
+impl<'a> TagVisitor<'a>
+{
+	/// Create a visitor with no closures
+	pub fn new() -> TagVisitor<'a>
+	{
+		TagVisitor
+		{
+			enter: Vec::new(),
+			leave: Vec::new(),
+		}
+	}
+
+	/// Call `f` when entering a node with tag `tag`
+	pub fn visit_tag<F>(mut self, tag: &str, f: F) -> TagVisitor<'a>
+		where F: FnMut(&Ast) + 'a
+	{
+		self.enter.push((tag.to_owned(), Box::new(f)));
+		self
+	}
+
+	/// Call `f` when leaving a node with tag `tag`
+	pub fn leave_tag<F>(mut self, tag: &str, f: F) -> TagVisitor<'a>
+		where F: FnMut(&Ast) + 'a
+	{
+		self.leave.push((tag.to_owned(), Box::new(f)));
+		self
+	}
+}
+
+impl<'a> Default for TagVisitor<'a>
+{
+	fn default() -> TagVisitor<'a>
+	{
+		TagVisitor::new()
+	}
+}
+
+impl<'a> Visitor for TagVisitor<'a>
+{
+	fn enter(&mut self, ast: &Ast)
+	{
+		for &mut (ref tag, ref mut f) in &mut self.enter
+		{
+			if ast.has_tag(tag) { f(ast); }
+		}
+	}
+
+	fn leave(&mut self, ast: &Ast)
+	{
+		for &mut (ref tag, ref mut f) in &mut self.leave
+		{
+			if ast.has_tag(tag) { f(ast); }
+		}
+	}
+}
+
+impl Ast
+{
+	/// Walk this node and all of its descendants depth-first,
+	/// calling `visitor`'s hooks on each of them
+	pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V)
+	{
+		// explicit stack so that deep trees can't overflow it,
+		// holds nodes along with the index of their next child
+		let mut stack: Vec<(&Ast, usize)> = vec![(self, 0)];
+		visitor.enter(self);
+
+		while let Some(top) = stack.last_mut()
+		{
+			let (node, next) = *top;
+			top.1 += 1;
+
+			if let Some(child) = node.children.get(next)
+			{
+				visitor.enter(child);
+				stack.push((child, 0));
+			}
+			else
+			{
+				visitor.leave(node);
+				stack.pop();
+			}
+		}
+	}
+
+	/// Rebuild this Ast bottom-up using `folder`. Spans of the
+	/// resulting nodes are those returned by `folder`
+	pub fn fold<F: Folder + ?Sized>(self, folder: &mut F) -> Ast
+	{
+		// nodes with their children taken out, the children
+		// left to fold and the ones already folded
+		let mut stack: Vec<(Ast, vec::IntoIter<Ast>, Vec<Ast>)> = Vec::new();
+		let mut current = self;
+
+		loop
+		{
+			let children = mem::take(&mut current.children).into_iter();
+			stack.push((current, children, Vec::new()));
+
+			loop
+			{
+				let next = stack.last_mut().and_then(|top| top.1.next());
+
+				if let Some(child) = next
+				{
+					current = child;
+					break;
+				}
+
+				let (mut node, _, folded) = stack.pop().unwrap();
+				node.children = folded;
+				let node = folder.fold(node);
+
+				match stack.last_mut()
+				{
+					Some(parent) => parent.2.push(node),
+					None => return node,
+				}
+			}
+		}
+	}
+}